@@ -0,0 +1,112 @@
+use astatine::apps::{Icon, build_applications};
+use astatine::config::{Config, HaystackField};
+use astatine::filter::filter_applications;
+use astatine::frecency::Frecency;
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+use freedesktop_desktop_entry::DesktopEntry;
+use std::hint::black_box;
+
+const SIZES: &[usize] = &[100, 1_000, 10_000];
+
+const WORDS: &[&str] = &[
+    "fire", "fox", "term", "text", "edit", "code", "music", "player", "office", "writer", "image",
+    "viewer", "mail", "chat", "files", "system", "monitor", "settings",
+];
+
+/// `n` entries with varied names, each with its own Exec so none are deduplicated
+fn fixture(n: usize) -> Vec<DesktopEntry> {
+    let locales: &[&str] = &[];
+
+    (0..n)
+        .map(|i| {
+            let first = WORDS[i % WORDS.len()];
+            let second = WORDS[(i / WORDS.len()) % WORDS.len()];
+            let input = format!(
+                "[Desktop Entry]\nType=Application\nName={first} {second} {i}\n\
+                 Exec={first}-{second}-{i} %U\nIcon={first}\nCategories=Utility;\n\
+                 Keywords={second};{first}{second};\n"
+            );
+
+            DesktopEntry::from_str(format!("/fixture/app{i}.desktop"), &input, Some(locales))
+                .expect("fixture entries are valid")
+        })
+        .collect()
+}
+
+fn build(c: &mut Criterion) {
+    let config = Config::default();
+    let mut group = c.benchmark_group("build_applications");
+
+    for &n in SIZES {
+        let entries = fixture(n);
+
+        group.bench_with_input(BenchmarkId::from_parameter(n), &entries, |b, entries| {
+            b.iter_batched(
+                || entries.clone(),
+                |entries| build_applications(entries, &[], &config, &Icon::None),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+fn filter(c: &mut Criterion) {
+    let config = Config::default();
+    let frecency = Frecency::default();
+    let mut group = c.benchmark_group("filter_applications");
+
+    for &n in SIZES {
+        let applications = build_applications(fixture(n), &[], &config, &Icon::None);
+
+        for query in ["fox", "mus pla", "settings 99"] {
+            group.bench_with_input(
+                BenchmarkId::new(query, n),
+                &applications,
+                |b, applications| {
+                    b.iter(|| {
+                        filter_applications(applications, black_box(query), &config, &frecency)
+                    })
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+/// The same queries matched once per app against the name and desktop ID joined, the
+/// fields `filter` scores with a matcher call each
+fn filter_haystack(c: &mut Criterion) {
+    let config = Config {
+        haystack_fields: vec![HaystackField::Name, HaystackField::Id],
+        ..Config::default()
+    };
+    let frecency = Frecency::default();
+    let mut group = c.benchmark_group("filter_applications_haystack");
+
+    for &n in SIZES {
+        let mut applications = build_applications(fixture(n), &[], &config, &Icon::None);
+        for application in &mut applications {
+            application.haystack = application.build_haystack(&config.haystack_fields);
+        }
+
+        for query in ["fox", "mus pla", "settings 99"] {
+            group.bench_with_input(
+                BenchmarkId::new(query, n),
+                &applications,
+                |b, applications| {
+                    b.iter(|| {
+                        filter_applications(applications, black_box(query), &config, &frecency)
+                    })
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, build, filter, filter_haystack);
+criterion_main!(benches);
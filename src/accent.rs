@@ -0,0 +1,77 @@
+use crate::apps::Icon;
+use iced::Color;
+use resvg::{tiny_skia, usvg};
+use std::fs;
+
+/// Side in pixels icons are shrunk to before their colors are averaged
+const SAMPLE_SIZE: u32 = 16;
+
+/// Pixels at most this opaque are left out, so transparent padding doesn't darken the mix
+const MIN_ALPHA: u8 = 64;
+
+/// The average color of an icon's visible pixels, `None` if it can't be read or is blank
+pub fn dominant_color(icon: &Icon) -> Option<Color> {
+    let pixels = match icon {
+        Icon::Svg(path) => svg_pixels(path)?,
+        Icon::Image(path) => image::open(path)
+            .ok()?
+            .thumbnail(SAMPLE_SIZE, SAMPLE_SIZE)
+            .to_rgba8()
+            .into_raw(),
+        Icon::None => return None,
+    };
+
+    average(&pixels)
+}
+
+/// Rasterize an SVG at the sample size, as straight RGBA
+fn svg_pixels(path: &str) -> Option<Vec<u8>> {
+    // `from_data` also takes the gzipped SVGZ some themes ship
+    let data = fs::read(path).ok()?;
+    let tree = usvg::Tree::from_data(&data, &usvg::Options::default()).ok()?;
+
+    let mut pixmap = tiny_skia::Pixmap::new(SAMPLE_SIZE, SAMPLE_SIZE)?;
+    let size = tree.size();
+    let scale = SAMPLE_SIZE as f32 / size.width().max(size.height());
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+
+    Some(
+        pixmap
+            .pixels()
+            .iter()
+            .flat_map(|pixel| {
+                let color = pixel.demultiply();
+                [color.red(), color.green(), color.blue(), color.alpha()]
+            })
+            .collect(),
+    )
+}
+
+/// Alpha weighted mean of RGBA pixels
+fn average(pixels: &[u8]) -> Option<Color> {
+    let mut sums = [0u64; 3];
+    let mut weight = 0u64;
+
+    for pixel in pixels.chunks_exact(4) {
+        let alpha = pixel[3];
+        if alpha <= MIN_ALPHA {
+            continue;
+        }
+
+        for (sum, channel) in sums.iter_mut().zip(pixel) {
+            *sum += u64::from(*channel) * u64::from(alpha);
+        }
+        weight += u64::from(alpha);
+    }
+
+    if weight == 0 {
+        return None;
+    }
+
+    let [r, g, b] = sums.map(|sum| (sum / weight) as u8);
+    Some(Color::from_rgb8(r, g, b))
+}
@@ -0,0 +1,604 @@
+use crate::cache;
+use crate::config::{Config, CustomEntry, DedupStrategy, HaystackField, XKeyBehavior};
+use crate::logger;
+#[cfg(feature = "transliteration")]
+use crate::transliterate;
+use freedesktop_desktop_entry::{DesktopEntry, Iter, default_paths};
+use freedesktop_icons::lookup;
+use icon_loader::IconLoader;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Application {
+    pub id: String,
+    /// Where the result comes from
+    #[serde(default)]
+    pub source: Source,
+    /// Whether the app is a Flatpak or Snap
+    #[serde(default)]
+    pub packaging: Packaging,
+    pub name: String,
+    /// Latin spelling of a CJK name, matched at a lower weight than the name itself
+    pub transliterated_name: Option<String>,
+    pub generic_name: Option<String>,
+    pub comment: Option<String>,
+    /// The entry's `Keywords`, e.g. "internet" and "www" for a browser
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// The `haystack_fields` joined, matched in one go instead of field by field
+    #[serde(skip)]
+    pub haystack: String,
+    pub exec: String,
+    /// Target of a `Type=Link` entry, opened with `xdg-open` instead of running `exec`
+    pub url: Option<String>,
+    /// The entry's Desktop Actions, like "New Private Window"
+    pub actions: Vec<DesktopAction>,
+    /// Set when running one of the actions, which only the Exec launch method can do
+    #[serde(default)]
+    pub from_action: bool,
+    /// Placeholder until the icon named by `icon_name` is resolved
+    #[serde(skip_deserializing, skip_serializing_if = "Icon::is_none")]
+    pub icon: Icon,
+    pub icon_name: String,
+    pub categories: Vec<String>,
+    /// Whether the program has to run inside a terminal
+    pub terminal: bool,
+    /// The `Path` key, the directory the program should run in
+    pub working_dir: Option<PathBuf>,
+    /// Values of the configured `X-` keys present on the entry
+    pub extras: HashMap<String, String>,
+    /// The `.desktop` file the entry was read from
+    pub source_path: Option<PathBuf>,
+    /// `StartupWMClass`, the class its windows have when it differs from the desktop ID
+    #[serde(default)]
+    pub wm_class: Option<String>,
+    /// `DBusActivatable`, the app can be started and raised over D-Bus
+    #[serde(default)]
+    pub dbus_activatable: bool,
+}
+
+impl Application {
+    pub fn score_boost(&self, config: &Config) -> i64 {
+        self.extras
+            .iter()
+            .filter(|(key, _)| config.x_keys.get(*key) == Some(&XKeyBehavior::Boost))
+            .filter_map(|(_, value)| value.trim().parse::<i64>().ok())
+            .sum()
+    }
+
+    /// The configured fields joined by spaces, in the configured order
+    pub fn build_haystack(&self, fields: &[HaystackField]) -> String {
+        let mut parts: Vec<&str> = Vec::new();
+
+        for field in fields {
+            match field {
+                HaystackField::Name => parts.push(&self.name),
+                HaystackField::GenericName => parts.extend(self.generic_name.as_deref()),
+                HaystackField::Keywords => parts.extend(self.keywords.iter().map(String::as_str)),
+                HaystackField::Comment => parts.extend(self.comment.as_deref()),
+                HaystackField::Id => parts.push(&self.id),
+            }
+        }
+
+        parts.join(" ")
+    }
+
+    /// What haystack matching runs against, the name for results built without one
+    pub fn haystack(&self) -> &str {
+        if self.haystack.is_empty() {
+            &self.name
+        } else {
+            &self.haystack
+        }
+    }
+
+    /// This application launching one of its Desktop Actions instead of its main Exec
+    pub fn with_action(&self, index: usize) -> Application {
+        let mut application = self.clone();
+        if let Some(action) = self.actions.get(index) {
+            application.exec = action.exec.clone();
+            application.url = None;
+            application.from_action = true;
+        }
+
+        application
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Source {
+    /// A `.desktop` file found in the scan
+    #[default]
+    Desktop,
+    /// An entry from the config's `[[entries]]`
+    Custom,
+    /// A line of a script's or the `filter_command`'s output
+    Script,
+    /// A file or directory listed for a path query
+    Path,
+    /// An open window listed by the `--windows` switcher
+    Window,
+}
+
+/// How an app was installed, told apart by where its entry lives or how it's run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Packaging {
+    #[default]
+    Native,
+    Flatpak,
+    Snap,
+}
+
+impl Packaging {
+    pub fn detect(path: &Path, exec: &str) -> Self {
+        let mut args = exec.split_whitespace();
+        let program = args.next().unwrap_or_default();
+        let mut runs =
+            |name| program.rsplit('/').next() == Some(name) && args.next() == Some("run");
+
+        if path.to_string_lossy().contains("/flatpak/exports/") || runs("flatpak") {
+            Self::Flatpak
+        } else if path.starts_with("/var/lib/snapd/")
+            || program.starts_with("/snap/bin/")
+            || runs("snap")
+        {
+            Self::Snap
+        } else {
+            Self::Native
+        }
+    }
+
+    /// The name used by `src:` queries and badges
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Native => "native",
+            Self::Flatpak => "flatpak",
+            Self::Snap => "snap",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        [Self::Native, Self::Flatpak, Self::Snap]
+            .into_iter()
+            .find(|packaging| packaging.label() == label)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DesktopAction {
+    pub name: String,
+    pub exec: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(untagged)]
+pub enum Icon {
+    Svg(String),
+    Image(String),
+    /// Results that aren't desktop entries, e.g. script output
+    #[default]
+    None,
+}
+
+impl Icon {
+    /// TOML has no way to write the unit variant, so it's left out of the cache
+    fn is_none(&self) -> bool {
+        matches!(self, Icon::None)
+    }
+}
+
+/// Directories scanned for entries, highest precedence first
+pub fn scan_paths(config: &Config) -> Vec<PathBuf> {
+    // `default_paths()` is $XDG_DATA_HOME followed by each of $XDG_DATA_DIRS
+    default_paths().chain(config.app_dirs()).collect()
+}
+
+/// Find an icon by name in the icon theme at the given size, or use an entry's own path
+pub fn resolve_icon(icon_name: &str, size: u16) -> Option<Icon> {
+    // Inline `data:` icons would need decoding, they keep the placeholder
+    if icon_name.starts_with("data:") {
+        return None;
+    }
+
+    let path = if Path::new(icon_name).is_absolute() {
+        PathBuf::from(icon_name)
+    } else {
+        lookup(icon_name).with_size(size).find()?
+    };
+    if !path.is_file() {
+        return None;
+    }
+
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    let svg = match extension.as_deref() {
+        Some("svg" | "svgz") => true,
+        Some("png" | "xpm" | "jpg" | "jpeg" | "gif" | "bmp" | "ico" | "webp") => false,
+        // Some themes ship icons without an extension
+        _ => sniff_svg(&path)?,
+    };
+
+    let path = path.to_string_lossy().into_owned();
+    if svg {
+        Some(Icon::Svg(path))
+    } else {
+        Some(Icon::Image(path))
+    }
+}
+
+/// `resolve_icon()` on its own thread, keeping the placeholder when it takes longer than
+/// `timeout`. A lookup stuck on a hung filesystem is left behind rather than waited for
+pub fn resolve_icon_within(icon_name: &str, size: u16, timeout: Duration) -> Option<Icon> {
+    if timeout.is_zero() {
+        return resolve_icon(icon_name, size);
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let name = icon_name.to_string();
+    thread::spawn(move || {
+        let _ = sender.send(resolve_icon(&name, size));
+    });
+
+    receiver.recv_timeout(timeout).unwrap_or_else(|_| {
+        logger::debug!("Looking up icon {} timed out", icon_name);
+        None
+    })
+}
+
+/// Whether a file's leading bytes say SVG or a raster format, `None` for neither
+fn sniff_svg(path: &Path) -> Option<bool> {
+    let mut header = [0; 512];
+    let read = fs::File::open(path)
+        .and_then(|mut file| file.read(&mut header))
+        .ok()?;
+    let header = &header[..read];
+
+    const RASTER_MAGIC: &[&[u8]] = &[
+        b"\x89PNG",
+        b"\xff\xd8\xff",
+        b"GIF8",
+        b"BM",
+        b"\x00\x00\x01\x00",
+        b"/* XPM */",
+    ];
+
+    if RASTER_MAGIC.iter().any(|magic| header.starts_with(magic))
+        || (header.starts_with(b"RIFF") && header.get(8..12) == Some(b"WEBP"))
+    {
+        Some(false)
+    } else if header.starts_with(b"\x1f\x8b") || header.windows(4).any(|w| w == b"<svg") {
+        // Gzip here is compressed SVG, the only compressed format themes use
+        Some(true)
+    } else {
+        None
+    }
+}
+
+/// The `.desktop` file behind `application` when it's the user's own to delete: directly
+/// in `~/.local/share/applications` and writable, never one installed system wide
+pub fn user_entry_path(application: &Application) -> Option<&Path> {
+    let path = application.source_path.as_deref()?;
+    let user_dir = dirs::data_dir()?.join("applications");
+    let writable = |path: &Path| {
+        path.symlink_metadata()
+            .is_ok_and(|meta| !meta.permissions().readonly())
+    };
+
+    (path.parent() == Some(user_dir.as_path()) && writable(path) && writable(&user_dir))
+        .then_some(path)
+}
+
+/// The user's locales, most preferred first, each followed by the forms the spec falls
+/// back to: `de_AT.UTF-8@euro` tries `de_AT@euro`, `de_AT`, `de@euro` and then `de`
+///
+/// Keys without any of these translations fall back to their untranslated value.
+pub fn locales() -> Vec<String> {
+    // `LANGUAGE` lists several in order, the others name one
+    let requested = env::var("LANGUAGE")
+        .into_iter()
+        .flat_map(|languages| languages.split(':').map(String::from).collect::<Vec<_>>())
+        .chain(
+            ["LC_ALL", "LC_MESSAGES", "LANG"]
+                .into_iter()
+                .filter_map(|var| env::var(var).ok())
+                .find(|locale| !locale.is_empty()),
+        );
+
+    let mut locales = Vec::new();
+    for locale in requested {
+        for fallback in locale_fallbacks(&locale) {
+            if !locales.contains(&fallback) {
+                locales.push(fallback);
+            }
+        }
+    }
+
+    locales
+}
+
+/// `lang_COUNTRY.ENCODING@MODIFIER` reduced the ways the spec's matching allows, the
+/// encoding never being part of a key
+fn locale_fallbacks(locale: &str) -> Vec<String> {
+    let (locale, modifier) = match locale.split_once('@') {
+        Some((locale, modifier)) => (locale, Some(modifier)),
+        None => (locale, None),
+    };
+    let locale = locale.split('.').next().unwrap_or(locale);
+    let (lang, country) = match locale.split_once('_') {
+        Some((lang, country)) => (lang, Some(country)),
+        None => (locale, None),
+    };
+
+    // "C" and "POSIX" mean untranslated
+    if lang.is_empty() || lang == "C" || lang == "POSIX" {
+        return Vec::new();
+    }
+
+    let mut fallbacks = Vec::new();
+    if let (Some(country), Some(modifier)) = (country, modifier) {
+        fallbacks.push(format!("{}_{}@{}", lang, country, modifier));
+    }
+    if let Some(country) = country {
+        fallbacks.push(format!("{}_{}", lang, country));
+    }
+    if let Some(modifier) = modifier {
+        fallbacks.push(format!("{}@{}", lang, modifier));
+    }
+    fallbacks.push(lang.to_string());
+
+    fallbacks
+}
+
+pub fn get_applications(config: &Config) -> Vec<Application> {
+    let locales = locales();
+    let paths = scan_paths(config);
+    let key = cache::key(&paths, &locales, config);
+
+    let icon_loader = IconLoader::new_gtk().unwrap_or_default();
+    // Without an icon theme there's nothing to fall back to, so those rows stay blank
+    let default_icon = match icon_loader.load_icon("application-x-executable") {
+        Some(icon) => Icon::Svg(
+            icon.file_for_size(config.icon_size)
+                .path()
+                .to_string_lossy()
+                .into_owned(),
+        ),
+        None => {
+            logger::warn!("No application-x-executable icon in the theme");
+            Icon::None
+        }
+    };
+
+    let mut applications = match cache::load(key) {
+        Some(mut applications) => {
+            for application in &mut applications {
+                application.icon = default_icon.clone();
+            }
+            logger::debug!("Loaded {} applications from the cache", applications.len());
+
+            applications
+        }
+        None => {
+            let mut entries = Iter::new(paths.into_iter())
+                .entries(Some(&locales))
+                .collect::<Vec<_>>();
+            if config.dedup_strategy != DedupStrategy::None {
+                drop_symlinked_duplicates(&mut entries);
+            }
+
+            let applications = build_applications(entries, &locales, config, &default_icon);
+            logger::debug!("Loaded {} applications", applications.len());
+            cache::store(key, &applications);
+
+            applications
+        }
+    };
+
+    // Read from the config each time, so they aren't cached with the scan
+    applications.extend(custom_applications(&config.entries, &default_icon));
+
+    if !config.haystack_fields.is_empty() {
+        for application in &mut applications {
+            application.haystack = application.build_haystack(&config.haystack_fields);
+        }
+    }
+
+    applications
+}
+
+/// Keep only the first entry read from each file, which distros symlinking `.desktop`
+/// files between directories give differing desktop IDs
+fn drop_symlinked_duplicates(entries: &mut Vec<DesktopEntry>) {
+    let mut seen = HashSet::new();
+
+    entries.retain(|entry| {
+        let target = fs::canonicalize(&entry.path).unwrap_or_else(|_| entry.path.clone());
+        let first = seen.insert(target);
+        if !first {
+            logger::debug!(
+                "Skipping {}, a link to an entry already read",
+                entry.path.display()
+            );
+        }
+        first
+    });
+}
+
+/// The config's own entries, skipping those with nothing to run or open
+fn custom_applications(entries: &[CustomEntry], default_icon: &Icon) -> Vec<Application> {
+    entries
+        .iter()
+        .filter(|entry| {
+            let runnable = entry.command.is_some() || entry.url.is_some();
+            if !runnable {
+                logger::warn!("Entry {} has neither a command nor a url", entry.name);
+            }
+            runnable
+        })
+        .map(|entry| Application {
+            id: format!("custom:{}", entry.name),
+            source: Source::Custom,
+            packaging: Packaging::Native,
+            name: entry.name.clone(),
+            transliterated_name: None,
+            generic_name: None,
+            comment: entry.comment.clone(),
+            keywords: Vec::new(),
+            haystack: String::new(),
+            exec: entry.command.clone().unwrap_or_default(),
+            url: entry.url.clone().filter(|_| entry.command.is_none()),
+            actions: Vec::new(),
+            from_action: false,
+            icon: default_icon.clone(),
+            icon_name: entry.icon.clone(),
+            categories: Vec::new(),
+            terminal: false,
+            working_dir: None,
+            extras: HashMap::new(),
+            source_path: None,
+            wm_class: None,
+            dbus_activatable: false,
+        })
+        .collect()
+}
+
+/// Turn parsed entries into applications, without touching the filesystem
+///
+/// Kept apart from `get_applications()` so it can run over synthetic entries.
+pub fn build_applications(
+    entries: Vec<DesktopEntry>,
+    locales: &[String],
+    config: &Config,
+    default_icon: &Icon,
+) -> Vec<Application> {
+    let mut applications = Vec::new();
+    let mut seen = HashSet::new();
+
+    for entry in entries {
+        let name = entry
+            .name(locales)
+            .map(|name| name.into_owned())
+            .unwrap_or_default();
+        // Exec is required but some entries ignore that, and D-Bus activatable ones
+        // may go without
+        let exec = entry.exec().unwrap_or("").to_string();
+        let dbus_activatable = entry.desktop_entry("DBusActivatable") == Some("true");
+        let icon_name = entry.icon().unwrap_or("").to_string();
+
+        let url = match entry.type_() {
+            Some("Directory") => continue,
+            Some("Link") => match entry.desktop_entry("URL").filter(|url| !url.is_empty()) {
+                Some(url) => Some(url.to_string()),
+                None => continue,
+            },
+            _ => None,
+        };
+
+        if name.is_empty() || (exec.is_empty() && url.is_none() && !dbus_activatable) {
+            continue;
+        }
+
+        let extras: HashMap<String, String> = config
+            .x_keys
+            .keys()
+            .filter_map(|key| Some((key.clone(), entry.desktop_entry(key)?.to_string())))
+            .collect();
+
+        let hidden = extras.iter().any(|(key, value)| {
+            config.x_keys.get(key) == Some(&XKeyBehavior::Hide) && value.trim() == "true"
+        });
+
+        // Earlier scan paths take precedence, so the first entry with a given key wins
+        let identity = match config.dedup_strategy {
+            // Entries without an Exec have nothing in common but that
+            DedupStrategy::Exec => Some(
+                url.clone()
+                    .or_else(|| Some(exec.clone()).filter(|exec| !exec.is_empty()))
+                    .unwrap_or_else(|| entry.appid.clone()),
+            ),
+            DedupStrategy::DesktopId => Some(entry.appid.clone()),
+            DedupStrategy::None => None,
+        };
+        if identity.is_some_and(|identity| !seen.insert(identity)) {
+            continue;
+        }
+
+        // Only after claiming its identity, so a hidden override hides the copies below it
+        if hidden {
+            continue;
+        }
+
+        #[cfg(feature = "transliteration")]
+        let transliterated_name = transliterate::transliterate(&name);
+        #[cfg(not(feature = "transliteration"))]
+        let transliterated_name = None;
+
+        applications.push(Application {
+            id: entry.appid.clone(),
+            source: Source::Desktop,
+            packaging: Packaging::detect(&entry.path, &exec),
+            name,
+            transliterated_name,
+            generic_name: entry.generic_name(locales).map(|name| name.into_owned()),
+            comment: entry.comment(locales).map(|comment| comment.into_owned()),
+            keywords: entry
+                .keywords(locales)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|keyword| !keyword.is_empty())
+                .map(|keyword| keyword.into_owned())
+                .collect(),
+            haystack: String::new(),
+            actions: entry
+                .actions()
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|action| {
+                    Some(DesktopAction {
+                        name: entry.action_name(action, locales)?.into_owned(),
+                        exec: entry.action_exec(action)?.to_string(),
+                    })
+                })
+                .collect(),
+            from_action: false,
+            exec,
+            url,
+            // Looked up later by `request_icons()`, only for results that are shown
+            icon: default_icon.clone(),
+            icon_name,
+            categories: entry
+                .categories()
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|category| !category.is_empty())
+                .map(String::from)
+                .collect(),
+            terminal: entry.terminal(),
+            working_dir: entry
+                .desktop_entry("Path")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            extras,
+            source_path: Some(entry.path.clone()),
+            wm_class: entry
+                .desktop_entry("StartupWMClass")
+                .filter(|class| !class.is_empty())
+                .map(String::from),
+            dbus_activatable,
+        });
+    }
+
+    applications
+}
@@ -0,0 +1,28 @@
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{AtomEnum, ConnectionExt, PropMode};
+use x11rb::wrapper::ConnectionExt as _;
+
+/// Ask the X11 compositor to blur what's behind `window`, by its X window ID
+///
+/// Sets KWin's `_KDE_NET_WM_BLUR_BEHIND_REGION` with no rectangles, meaning all of
+/// the window. Compositors that don't know the hint leave the window plainly
+/// translucent.
+pub fn request(window: u32) -> Result<(), String> {
+    let (conn, _) = x11rb::connect(None).map_err(|e| e.to_string())?;
+    let blur_region = conn
+        .intern_atom(false, b"_KDE_NET_WM_BLUR_BEHIND_REGION")
+        .map_err(|e| e.to_string())?
+        .reply()
+        .map_err(|e| e.to_string())?
+        .atom;
+
+    conn.change_property32(
+        PropMode::REPLACE,
+        window,
+        blur_region,
+        AtomEnum::CARDINAL,
+        &[],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.flush().map_err(|e| e.to_string())
+}
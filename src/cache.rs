@@ -0,0 +1,101 @@
+use crate::apps::Application;
+use crate::config::Config;
+use crate::logger;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// The parsed application list as of the last scan, reused while no entry changed
+#[derive(Serialize, Deserialize)]
+struct Cache {
+    /// Hex, TOML integers can't hold every `u64`
+    key: String,
+    applications: Vec<Application>,
+}
+
+/// Bumped when applications gain fields that older caches lack, or other entries are kept
+const FORMAT: u32 = 4;
+
+/// Fingerprint of everything the application list is built from: every file under the
+/// scanned directories with its mtime, the locales and the options affecting the build
+pub fn key(paths: &[PathBuf], locales: &[String], config: &Config) -> u64 {
+    let mut hasher = DefaultHasher::new();
+
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    FORMAT.hash(&mut hasher);
+    cfg!(feature = "transliteration").hash(&mut hasher);
+    locales.hash(&mut hasher);
+    config.dedup_strategy.hash(&mut hasher);
+
+    // Sorted, map iteration order differs between runs
+    let mut x_keys: Vec<String> = config
+        .x_keys
+        .iter()
+        .map(|(key, behavior)| format!("{}={:?}", key, behavior))
+        .collect();
+    x_keys.sort();
+    x_keys.hash(&mut hasher);
+
+    for path in paths {
+        hash_tree(path, &mut hasher);
+    }
+
+    hasher.finish()
+}
+
+/// Hash the paths and mtimes below `dir`, in a stable order
+fn hash_tree(dir: &Path, hasher: &mut DefaultHasher) {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return;
+    };
+
+    let mut entries: Vec<PathBuf> = read_dir.flatten().map(|entry| entry.path()).collect();
+    entries.sort();
+
+    for path in entries {
+        path.hash(hasher);
+        if let Ok(modified) = path.metadata().and_then(|meta| meta.modified()) {
+            modified.hash(hasher);
+        }
+
+        if path.is_dir() {
+            hash_tree(&path, hasher);
+        }
+    }
+}
+
+/// The cached applications if they were built from the same files and options
+pub fn load(key: u64) -> Option<Vec<Application>> {
+    let contents = fs::read_to_string(cache_path()?).ok()?;
+    let cache: Cache = toml::from_str(&contents).ok()?;
+
+    (cache.key == format!("{:x}", key)).then_some(cache.applications)
+}
+
+pub fn store(key: u64, applications: &[Application]) {
+    let Some(path) = cache_path() else {
+        return;
+    };
+
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+
+    let cache = Cache {
+        key: format!("{:x}", key),
+        applications: applications.to_vec(),
+    };
+    let written = toml::to_string(&cache)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| fs::write(&path, contents).map_err(|e| e.to_string()));
+
+    if let Err(e) = written {
+        logger::warn!("Failed to write {}: {}", path.display(), e);
+    }
+}
+
+fn cache_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("astatine").join("applications.toml"))
+}
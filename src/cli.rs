@@ -0,0 +1,64 @@
+use crate::logger::Level;
+use std::env;
+use std::path::PathBuf;
+
+#[derive(Debug, Default)]
+pub struct Args {
+    pub no_dedup: bool,
+    pub dump_json: bool,
+    pub print_paths: bool,
+    pub reset_history: bool,
+    /// Show sample results instead of the installed apps, for tuning colors
+    pub theme_preview: bool,
+    /// A `.desktop` file to check instead of opening the launcher
+    pub validate: Option<PathBuf>,
+    /// Mode to start in, e.g. `command` or a script's trigger
+    pub mode: Option<String>,
+    /// Desktop ID to launch without showing a window
+    pub launch: Option<String>,
+    /// Browse without ever launching anything
+    pub safe: bool,
+    /// List open windows instead of apps and switch to the chosen one
+    pub windows: bool,
+    /// Set by `--quiet` or `--verbose`, overriding the config's `log_level`
+    pub log_level: Option<Level>,
+    /// Reported once the log level is known
+    pub unknown: Vec<String>,
+}
+
+impl Args {
+    pub fn parse() -> Self {
+        let mut args = Self::default();
+
+        let mut argv = env::args().skip(1);
+
+        while let Some(arg) = argv.next() {
+            match arg.as_str() {
+                "--no-dedup" => args.no_dedup = true,
+                "--dump-json" => args.dump_json = true,
+                "--print-paths" => args.print_paths = true,
+                "--reset-history" => args.reset_history = true,
+                "--theme-preview" => args.theme_preview = true,
+                "--safe" => args.safe = true,
+                "--windows" => args.windows = true,
+                "--validate" => match argv.next() {
+                    Some(path) => args.validate = Some(PathBuf::from(path)),
+                    None => args.unknown.push(arg),
+                },
+                "--mode" => match argv.next() {
+                    Some(mode) => args.mode = Some(mode),
+                    None => args.unknown.push(arg),
+                },
+                "--launch" => match argv.next() {
+                    Some(id) => args.launch = Some(id),
+                    None => args.unknown.push(arg),
+                },
+                "--quiet" => args.log_level = Some(Level::Error),
+                "--verbose" => args.log_level = Some(Level::Debug),
+                _ => args.unknown.push(arg),
+            }
+        }
+
+        args
+    }
+}
@@ -0,0 +1,93 @@
+use crate::logger;
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use std::fs;
+use std::path::PathBuf;
+
+const MAX_HISTORY: usize = 200;
+
+/// Shell commands run from `>` mode, oldest first
+#[derive(Debug, Default)]
+pub struct CommandHistory {
+    commands: Vec<String>,
+}
+
+impl CommandHistory {
+    pub fn load() -> Self {
+        let commands = history_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|contents| {
+                contents
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self { commands }
+    }
+
+    /// Move `command` to the most recent position and persist the history
+    pub fn record(&mut self, command: &str) {
+        let command = command.trim();
+        if command.is_empty() {
+            return;
+        }
+
+        self.commands.retain(|c| c != command);
+        self.commands.push(command.to_string());
+
+        let overflow = self.commands.len().saturating_sub(MAX_HISTORY);
+        self.commands.drain(..overflow);
+
+        self.save();
+    }
+
+    /// The `n`th most recent command
+    pub fn recent(&self, n: usize) -> Option<&str> {
+        self.commands.iter().rev().nth(n).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Past commands matching `query`, most recent first
+    pub fn suggestions(&self, query: &str) -> Vec<String> {
+        let query = query.trim();
+        let matcher = SkimMatcherV2::default();
+
+        self.commands
+            .iter()
+            .rev()
+            .filter(|command| query.is_empty() || matcher.fuzzy_match(command, query).is_some())
+            .cloned()
+            .collect()
+    }
+
+    fn save(&self) {
+        let Some(path) = history_path() else {
+            return;
+        };
+
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+
+        let mut contents = self.commands.join("\n");
+        contents.push('\n');
+
+        if let Err(e) = fs::write(&path, contents) {
+            logger::warn!("Failed to write {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("astatine").join("command_history"))
+}
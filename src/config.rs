@@ -0,0 +1,598 @@
+use crate::cli::Args;
+use crate::logger::{self, Level};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_CONFIG: &str = include_str!("default_config.toml");
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Directories scanned for `.desktop` files in addition to `default_paths()`
+    pub extra_app_dirs: Vec<PathBuf>,
+    /// Which entries count as the same app, only the first of them being shown
+    pub dedup_strategy: DedupStrategy,
+    /// Show every entry, the same as `dedup_strategy = "none"`
+    pub no_dedup: bool,
+    /// Log what selecting a result would run instead of running it, and keep the
+    /// launch history as it is
+    pub safe_mode: bool,
+    /// Command prepended to the Exec of matching desktop IDs, keyed by ID or glob
+    pub launch_prefix: HashMap<String, String>,
+    /// Arguments standing in for `%f`, `%F`, `%u` and `%U` of the app with a given
+    /// desktop ID, which are otherwise dropped since no file is ever passed
+    pub default_args: HashMap<String, String>,
+    /// Environment variables set when launching the app with a given desktop ID
+    pub launch_env: HashMap<String, HashMap<String, String>>,
+    /// CPU and I/O priority matching desktop IDs are launched with, keyed by ID or glob
+    pub launch_priority: HashMap<String, LaunchPriority>,
+    /// Vendor `X-` keys to read from entries and what to do with their values
+    pub x_keys: HashMap<String, XKeyBehavior>,
+    /// How queries are matched against names, queries starting with `re:` always use regex
+    pub match_mode: MatchMode,
+    /// Characters a query needs before it filters, shorter ones show `short_query` results
+    pub min_query_len: usize,
+    /// What queries shorter than `min_query_len` show
+    pub short_query: ShortQuery,
+    /// Working directory for apps whose entry has no `Path` key
+    pub default_working_dir: Option<PathBuf>,
+    /// Enter in the search box acts on the top result instead of only focusing it
+    pub enter_launches_top: bool,
+    /// Select the most recently launched app while the query is empty
+    pub focus_last_used: bool,
+    /// Select the query whenever the search box is focused with text in it, so typing
+    /// replaces it
+    pub select_on_focus: bool,
+    /// Enter raises an open window of the app instead of launching it again, unless
+    /// Alt is held
+    pub raise_existing: bool,
+    /// Whether a single click launches a result or only focuses it until clicked again
+    pub click_mode: ClickMode,
+    /// How desktop entries are started: running their Exec, possibly in a systemd scope,
+    /// or handing their ID to `gtk-launch` or D-Bus activation
+    pub launch_method: LaunchMethod,
+    /// Milliseconds during which launching the same app again is ignored, 0 allows it
+    pub duplicate_launch_window_ms: u64,
+    /// Milliseconds the window stays open showing "Launching…" after a launch, so the
+    /// compositor hands focus to the new app rather than back to the previous window
+    pub close_delay_ms: u64,
+    /// Shell command run after an app launches, with `$ASTATINE_ID`, `$ASTATINE_NAME`
+    /// and `$ASTATINE_EXEC` describing it
+    pub post_launch_hook: Option<String>,
+    /// Size in pixels icons are looked up and drawn at, e.g. 16, 24, 32 or 48
+    pub icon_size: u16,
+    /// Icon lookups running at once, the rest wait in a queue
+    pub icon_concurrency: usize,
+    /// Milliseconds after which an icon lookup gives up and keeps the placeholder, 0 waits
+    pub icon_lookup_timeout_ms: u64,
+    /// What results show while their icon is looked up
+    pub icon_placeholder: IconPlaceholder,
+    /// How each result line is composed from `{name}`, `{generic_name}`, `{comment}`,
+    /// `{exec}`, `{id}` and `{categories}`
+    pub result_format: String,
+    pub layout: Layout,
+    /// Results per row, more than 1 lays them out as a grid of icons
+    pub columns: usize,
+    /// Exact height in pixels of each row in the list, its contents centered in it.
+    /// Unset fits the row to its contents
+    pub row_height: Option<u16>,
+    /// Pixels between a result's icon and its name
+    pub icon_text_gap: u16,
+    /// Show the main keybindings in a footer
+    pub show_hints: bool,
+    /// Mark Flatpak and Snap apps with a badge
+    pub package_badges: bool,
+    /// Badge each launched result with its launch count, colored by how recently
+    pub show_usage: bool,
+    /// Show a row of category chips that restrict the results to one category
+    pub category_chips: bool,
+    /// Icons of this many most used apps shown above the search box, 0 hides the bar
+    pub quick_launch: usize,
+    /// Mark each result with a bar in its icon's dominant color
+    pub accent_colors: bool,
+    /// Milliseconds the highlight takes to fade over when the focus moves, 0 moves it
+    /// instantly
+    pub selection_animation_ms: u64,
+    /// Opacity of the window's background from 0 to 1, below 1 the window is created
+    /// transparent for compositors to show what's behind it
+    pub window_opacity: f32,
+    /// Ask the compositor to blur behind the window, which only shows below full opacity
+    pub blur: bool,
+    /// Fall back to edit distance ranking when fuzzy matching finds nothing good
+    pub typo_tolerance: bool,
+    /// Best fuzzy score at or below which the typo fallback kicks in
+    pub typo_score_threshold: i64,
+    /// Multiplier for the launch history bonus added to match scores, 0 disables it
+    pub frecency_weight: f64,
+    /// Exponent of the name to query length ratio fuzzy scores are scaled by, positive
+    /// values lift long names and negative ones favor short names
+    pub length_normalization: f64,
+    /// Fields joined into one string fuzzy queries are matched against once, instead of
+    /// scoring the name, transliteration and desktop ID separately. Empty keeps the latter
+    pub haystack_fields: Vec<HaystackField>,
+    /// Shell command matching queries instead of the built-in matcher, fed the query on
+    /// stdin and printing a desktop ID, `name<TAB>command` or something to open per line
+    pub filter_command: Option<String>,
+    /// Milliseconds the query has to stay unchanged before `filter_command` runs
+    pub filter_debounce_ms: u64,
+    pub terminal: TerminalConfig,
+    pub focus_ring: FocusRingConfig,
+    pub search_box: SearchBoxConfig,
+    /// External commands whose output lines become results behind a trigger prefix
+    pub scripts: Vec<ScriptConfig>,
+    /// Results defined in the config, like bookmarks, listed along with the apps
+    pub entries: Vec<CustomEntry>,
+    /// Desktop IDs or globs of the apps Ctrl+F narrows the results down to
+    pub favorites: Vec<String>,
+    /// Force Wayland or X11 window behavior instead of detecting the session, which
+    /// doesn't change the display server the window opens on
+    pub session: SessionOverride,
+    /// How much is printed to stderr: "error", "warn", "info" or "debug"
+    pub log_level: Level,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionOverride {
+    #[default]
+    Auto,
+    Wayland,
+    X11,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScriptConfig {
+    /// Query prefix that switches the results to this script's lines
+    pub trigger: String,
+    /// Shell command printing one result per line
+    pub command: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CustomEntry {
+    pub name: String,
+    /// Run when chosen, quoted like a desktop entry's Exec
+    pub command: Option<String>,
+    /// Opened with `xdg-open` when chosen, if there is no command
+    pub url: Option<String>,
+    /// Icon name from the theme, or a path to an image
+    #[serde(default)]
+    pub icon: String,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TerminalConfig {
+    /// Emulator for `Terminal=true` apps, defaults to `$TERMINAL` and then `xterm`
+    pub command: Option<String>,
+    /// Flag preceding the program to run, `-e` for most emulators
+    pub exec_flag: String,
+    /// Keep the terminal open after the program exits
+    pub hold: bool,
+    /// The emulator's own hold flag, guessed for common emulators
+    pub hold_flag: Option<String>,
+}
+
+impl Default for TerminalConfig {
+    fn default() -> Self {
+        Self {
+            command: None,
+            exec_flag: String::from("-e"),
+            hold: false,
+            hold_flag: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FocusRingConfig {
+    /// Border color of the focused result as `#rrggbb` or `#rrggbbaa`
+    pub color: String,
+    /// Border width in pixels, 0 leaves only the background highlight
+    pub width: f32,
+}
+
+impl Default for FocusRingConfig {
+    fn default() -> Self {
+        Self {
+            color: String::from("#ff9e64"),
+            width: 2.0,
+        }
+    }
+}
+
+/// Overrides for the search box's look, unset fields keep the theme's
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SearchBoxConfig {
+    /// Background color as `#rrggbb` or `#rrggbbaa`
+    pub background: Option<String>,
+    pub border_color: Option<String>,
+    pub border_width: Option<f32>,
+    pub border_radius: Option<f32>,
+    /// Text shown while the query is empty
+    pub placeholder: String,
+    pub placeholder_color: Option<String>,
+    /// Show a glyph for the query's mode at the left, a magnifier while searching apps
+    pub icon: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            extra_app_dirs: Vec::new(),
+            dedup_strategy: DedupStrategy::default(),
+            no_dedup: false,
+            safe_mode: false,
+            launch_prefix: HashMap::new(),
+            default_args: HashMap::new(),
+            launch_env: HashMap::new(),
+            launch_priority: HashMap::new(),
+            x_keys: HashMap::new(),
+            match_mode: MatchMode::default(),
+            min_query_len: 0,
+            short_query: ShortQuery::default(),
+            default_working_dir: None,
+            enter_launches_top: true,
+            focus_last_used: false,
+            select_on_focus: false,
+            raise_existing: false,
+            click_mode: ClickMode::default(),
+            launch_method: LaunchMethod::default(),
+            duplicate_launch_window_ms: 300,
+            close_delay_ms: 0,
+            post_launch_hook: None,
+            icon_size: 32,
+            icon_concurrency: 4,
+            icon_lookup_timeout_ms: 2000,
+            icon_placeholder: IconPlaceholder::default(),
+            result_format: String::from("{name}"),
+            layout: Layout::default(),
+            columns: 1,
+            row_height: None,
+            icon_text_gap: 10,
+            show_hints: false,
+            package_badges: false,
+            show_usage: false,
+            category_chips: false,
+            quick_launch: 0,
+            accent_colors: false,
+            selection_animation_ms: 0,
+            window_opacity: 1.0,
+            blur: false,
+            typo_tolerance: false,
+            typo_score_threshold: 0,
+            frecency_weight: 5.0,
+            length_normalization: 0.0,
+            haystack_fields: Vec::new(),
+            filter_command: None,
+            filter_debounce_ms: 150,
+            terminal: TerminalConfig::default(),
+            focus_ring: FocusRingConfig::default(),
+            search_box: SearchBoxConfig::default(),
+            scripts: Vec::new(),
+            entries: Vec::new(),
+            favorites: Vec::new(),
+            session: SessionOverride::default(),
+            log_level: Level::Info,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IconPlaceholder {
+    /// The generic application icon, also shown when none is found
+    #[default]
+    Generic,
+    /// A neutral rounded square
+    Box,
+    /// Empty space of the icon's size
+    None,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    /// Search box above spaced out results
+    #[default]
+    List,
+    /// Tighter rows with an inline prompt, like rofi
+    Compact,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    #[default]
+    Fuzzy,
+    Regex,
+    /// Names containing the query as is, toggled with Ctrl+S
+    Substring,
+}
+
+/// A field of an application that can join its `haystack_fields`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HaystackField {
+    Name,
+    GenericName,
+    Keywords,
+    Comment,
+    Id,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DedupStrategy {
+    /// Entries running the same command or opening the same URL
+    Exec,
+    /// Entries with the same desktop ID, where the user's copy overrides the system's
+    #[default]
+    DesktopId,
+    None,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LaunchMethod {
+    #[default]
+    Exec,
+    /// `gtk-launch <desktop-id>`, which registers the app like the desktop would
+    GtkLaunch,
+    /// `org.freedesktop.Application.Activate` for `DBusActivatable` apps
+    Dbus,
+    /// The Exec inside its own `systemd-run --user --scope` cgroup
+    SystemdScope,
+}
+
+/// How an app is scheduled, set in its process before it runs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct LaunchPriority {
+    /// Niceness from -20, scheduled first, to 19. Below 0 needs privileges
+    pub nice: Option<i8>,
+    pub ionice: Option<IoClass>,
+}
+
+/// An I/O scheduling class, as with `ionice`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IoClass {
+    /// Served before everything else, needs privileges
+    Realtime,
+    BestEffort,
+    /// Served only while nothing else uses the disk
+    Idle,
+}
+
+impl IoClass {
+    /// The class's number, as the kernel and `ionice -c` know it
+    pub fn number(self) -> u8 {
+        match self {
+            IoClass::Realtime => 1,
+            IoClass::BestEffort => 2,
+            IoClass::Idle => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClickMode {
+    /// Launch on the first click
+    #[default]
+    Single,
+    /// Focus on the first click and launch on a second one in quick succession
+    Double,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShortQuery {
+    /// Every application, as for an empty query
+    #[default]
+    All,
+    /// No results until the query is long enough
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum XKeyBehavior {
+    /// Hide the entry when the value is `true`
+    Hide,
+    /// Add the integer value to the entry's match score
+    Boost,
+}
+
+impl Config {
+    pub fn apply_args(&mut self, args: &Args) {
+        if args.no_dedup {
+            self.no_dedup = true;
+        }
+        if self.no_dedup {
+            self.dedup_strategy = DedupStrategy::None;
+        }
+
+        if args.safe {
+            self.safe_mode = true;
+        }
+
+        if let Some(level) = args.log_level {
+            self.log_level = level;
+        }
+    }
+
+    pub fn load() -> Self {
+        let Some(path) = config_path() else {
+            return Self::default();
+        };
+
+        if !path.exists() {
+            write_default(&path);
+            return Self::default();
+        }
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(_) => return Self::default(),
+        };
+
+        match toml::from_str::<Config>(&contents) {
+            Ok(mut config) => {
+                config.extra_app_dirs = config
+                    .extra_app_dirs
+                    .into_iter()
+                    .map(|dir| expand_home(&dir))
+                    .collect();
+                config.default_working_dir =
+                    config.default_working_dir.map(|dir| expand_home(&dir));
+
+                if iced::Color::parse(&config.focus_ring.color).is_none() {
+                    logger::warn!(
+                        "Invalid focus ring color {}, using the default",
+                        config.focus_ring.color
+                    );
+                    config.focus_ring.color = FocusRingConfig::default().color;
+                }
+
+                config.columns = config.columns.max(1);
+                config.icon_concurrency = config.icon_concurrency.max(1);
+
+                if config.raise_existing && !cfg!(feature = "window-count") {
+                    logger::warn!(
+                        "raise_existing requires building with the `window-count` feature"
+                    );
+                }
+
+                let search_box = &mut config.search_box;
+                for color in [
+                    &mut search_box.background,
+                    &mut search_box.border_color,
+                    &mut search_box.placeholder_color,
+                ] {
+                    if let Some(invalid) = color.take_if(|c| iced::Color::parse(c).is_none()) {
+                        logger::warn!("Invalid search box color {}, using the theme's", invalid);
+                    }
+                }
+
+                config
+            }
+            Err(e) => {
+                logger::warn!("Failed to parse {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Extra application directories that exist, warning about the ones that don't
+    pub fn app_dirs(&self) -> Vec<PathBuf> {
+        self.extra_app_dirs
+            .iter()
+            .filter(|dir| {
+                if dir.is_dir() {
+                    true
+                } else {
+                    logger::warn!("Skipping missing application directory {}", dir.display());
+                    false
+                }
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Whether a desktop ID is listed in `favorites`, itself or by a glob
+    pub fn is_favorite(&self, id: &str) -> bool {
+        self.favorites
+            .iter()
+            .any(|pattern| pattern == id || glob_match(pattern, id))
+    }
+
+    /// The launch prefix for a desktop ID, preferring an exact key over the most specific glob
+    pub fn launch_prefix_for(&self, id: &str) -> Option<&str> {
+        lookup_id(&self.launch_prefix, id).map(String::as_str)
+    }
+
+    /// The launch priority for a desktop ID, picked like `launch_prefix_for()`
+    pub fn launch_priority_for(&self, id: &str) -> Option<LaunchPriority> {
+        lookup_id(&self.launch_priority, id).copied()
+    }
+}
+
+/// The value keyed by `id` itself, or else by the longest glob matching it
+fn lookup_id<'a, T>(map: &'a HashMap<String, T>, id: &str) -> Option<&'a T> {
+    map.get(id).or_else(|| {
+        map.iter()
+            .filter(|(pattern, _)| glob_match(pattern, id))
+            .max_by_key(|(pattern, _)| pattern.len())
+            .map(|(_, value)| value)
+    })
+}
+
+/// Give new users a commented template listing every option
+fn write_default(path: &Path) {
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+
+    // `create_new` so a config appearing in the meantime is never overwritten
+    let written = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(DEFAULT_CONFIG.as_bytes()));
+
+    match written {
+        Ok(()) => logger::info!("Wrote default config to {}", path.display()),
+        Err(e) => logger::warn!("Failed to write default config {}: {}", path.display(), e),
+    }
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("astatine").join("config.toml"))
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Match `text` against a pattern where `*` is any run of characters and `?` any one
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,198 @@
+use crate::logger;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dimension {
+    Length,
+    Mass,
+    Temperature,
+    Time,
+    Volume,
+    Data,
+}
+
+struct Unit {
+    names: &'static [&'static str],
+    dimension: Dimension,
+    /// Multiplier to the dimension's base unit
+    scale: f64,
+    /// Added after scaling, only non-zero for temperatures
+    offset: f64,
+}
+
+const UNITS: &[Unit] = &[
+    unit(
+        &["mm", "millimeter", "millimeters"],
+        Dimension::Length,
+        0.001,
+    ),
+    unit(
+        &["cm", "centimeter", "centimeters"],
+        Dimension::Length,
+        0.01,
+    ),
+    unit(&["m", "meter", "meters"], Dimension::Length, 1.0),
+    unit(
+        &["km", "kilometer", "kilometers"],
+        Dimension::Length,
+        1000.0,
+    ),
+    unit(&["in", "inch", "inches"], Dimension::Length, 0.0254),
+    unit(&["ft", "foot", "feet"], Dimension::Length, 0.3048),
+    unit(&["yd", "yard", "yards"], Dimension::Length, 0.9144),
+    unit(&["mi", "mile", "miles"], Dimension::Length, 1609.344),
+    unit(&["mg", "milligram", "milligrams"], Dimension::Mass, 0.001),
+    unit(&["g", "gram", "grams"], Dimension::Mass, 1.0),
+    unit(&["kg", "kilogram", "kilograms"], Dimension::Mass, 1000.0),
+    unit(&["oz", "ounce", "ounces"], Dimension::Mass, 28.349523125),
+    unit(
+        &["lb", "lbs", "pound", "pounds"],
+        Dimension::Mass,
+        453.59237,
+    ),
+    Unit {
+        names: &["c", "celsius"],
+        dimension: Dimension::Temperature,
+        scale: 1.0,
+        offset: 273.15,
+    },
+    Unit {
+        names: &["f", "fahrenheit"],
+        dimension: Dimension::Temperature,
+        scale: 5.0 / 9.0,
+        offset: 459.67 * 5.0 / 9.0,
+    },
+    unit(&["k", "kelvin"], Dimension::Temperature, 1.0),
+    unit(
+        &["ms", "millisecond", "milliseconds"],
+        Dimension::Time,
+        0.001,
+    ),
+    unit(&["s", "sec", "second", "seconds"], Dimension::Time, 1.0),
+    unit(&["min", "minute", "minutes"], Dimension::Time, 60.0),
+    unit(&["h", "hour", "hours"], Dimension::Time, 3600.0),
+    unit(&["d", "day", "days"], Dimension::Time, 86400.0),
+    unit(
+        &["ml", "milliliter", "milliliters"],
+        Dimension::Volume,
+        0.001,
+    ),
+    unit(&["l", "liter", "liters"], Dimension::Volume, 1.0),
+    unit(
+        &["gal", "gallon", "gallons"],
+        Dimension::Volume,
+        3.785411784,
+    ),
+    unit(&["b", "byte", "bytes"], Dimension::Data, 1.0),
+    unit(&["kb", "kilobyte", "kilobytes"], Dimension::Data, 1e3),
+    unit(&["mb", "megabyte", "megabytes"], Dimension::Data, 1e6),
+    unit(&["gb", "gigabyte", "gigabytes"], Dimension::Data, 1e9),
+    unit(&["kib", "kibibyte", "kibibytes"], Dimension::Data, 1024.0),
+    unit(
+        &["mib", "mebibyte", "mebibytes"],
+        Dimension::Data,
+        1048576.0,
+    ),
+    unit(
+        &["gib", "gibibyte", "gibibytes"],
+        Dimension::Data,
+        1073741824.0,
+    ),
+];
+
+const fn unit(names: &'static [&'static str], dimension: Dimension, scale: f64) -> Unit {
+    Unit {
+        names,
+        dimension,
+        scale,
+        offset: 0.0,
+    }
+}
+
+/// Cached exchange rates, each currency's value relative to a shared base
+#[derive(Debug, Default, Deserialize)]
+struct Rates {
+    rates: HashMap<String, f64>,
+}
+
+/// Convert queries like `10 km in miles` or `100 usd to eur`
+pub fn convert(query: &str) -> Option<String> {
+    let (amount, from, to) = parse(query)?;
+
+    let converted = match (find_unit(&from), find_unit(&to)) {
+        (Some(from), Some(to)) if from.dimension == to.dimension => {
+            let base = amount * from.scale + from.offset;
+            (base - to.offset) / to.scale
+        }
+        (None, None) => {
+            let rates = load_rates()?;
+            let from_rate = rates.rates.get(&from.to_uppercase())?;
+            let to_rate = rates.rates.get(&to.to_uppercase())?;
+            amount / from_rate * to_rate
+        }
+        _ => return None,
+    };
+
+    Some(format!(
+        "{} {} = {} {}",
+        format_number(amount),
+        from,
+        format_number(converted),
+        to
+    ))
+}
+
+fn parse(query: &str) -> Option<(f64, String, String)> {
+    let tokens: Vec<&str> = query.split_whitespace().collect();
+
+    // Either `10 km to mi` or `10km to mi`
+    let (amount, from, rest) = match tokens.as_slice() {
+        [amount, from, rest @ ..] if amount.parse::<f64>().is_ok() => {
+            (amount.parse().ok()?, from.to_string(), rest)
+        }
+        [first, rest @ ..] => {
+            let split = first.find(|c: char| c.is_alphabetic())?;
+            let (amount, from) = first.split_at(split);
+            (amount.parse().ok()?, from.to_string(), rest)
+        }
+        [] => return None,
+    };
+
+    match rest {
+        [keyword, to] if matches!(*keyword, "to" | "in") => {
+            Some((amount, from.to_lowercase(), to.to_lowercase()))
+        }
+        _ => None,
+    }
+}
+
+fn find_unit(name: &str) -> Option<&'static Unit> {
+    UNITS.iter().find(|unit| unit.names.contains(&name))
+}
+
+fn rates_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("astatine").join("currency_rates.toml"))
+}
+
+fn load_rates() -> Option<Rates> {
+    let contents = fs::read_to_string(rates_path()?).ok()?;
+
+    match toml::from_str(&contents) {
+        Ok(rates) => Some(rates),
+        Err(e) => {
+            logger::warn!("Failed to parse currency rates: {}", e);
+            None
+        }
+    }
+}
+
+fn format_number(value: f64) -> String {
+    let formatted = format!("{:.4}", value);
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
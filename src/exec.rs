@@ -0,0 +1,268 @@
+use std::env;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+/// Shells whose `-c` command is passed on as a single argument
+const SHELLS: &[&str] = &["sh", "bash", "dash", "zsh", "ksh", "fish"];
+
+/// Program and arguments of an Exec line, with `%%` collapsed to a literal `%` and
+/// field codes expanded: file and URL codes to `file_args`, all others to nothing
+///
+/// Quoted arguments are taken as they are, field codes aren't allowed in them.
+pub fn exec_args(exec: &str, file_args: &[String]) -> Vec<String> {
+    split_arguments(exec)
+        .into_iter()
+        .flat_map(|(part, quoted)| {
+            if quoted {
+                vec![part.replace("%%", "%")]
+            } else {
+                expand_field_codes(&part, file_args)
+            }
+        })
+        .collect()
+}
+
+/// Split an Exec line into arguments and whether each was quoted
+///
+/// Double quotes group an argument, with `\"`, `` \` ``, `\$` and `\\` escaping
+/// those characters. The command after a shell's `-c` may also be single quoted,
+/// as many entries write `sh -c 'cmd | other'` although the spec doesn't allow it.
+fn split_arguments(exec: &str) -> Vec<(String, bool)> {
+    let mut parts: Vec<(String, bool)> = Vec::new();
+    let mut chars = exec.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(&first) = chars.peek() else {
+            break;
+        };
+
+        let quote = match first {
+            '"' => Some('"'),
+            '\'' if is_shell_command(&parts) => Some('\''),
+            _ => None,
+        };
+
+        let mut part = String::new();
+        if let Some(quote) = quote {
+            chars.next();
+            while let Some(c) = chars.next() {
+                if c == quote {
+                    break;
+                }
+
+                let escaped = (quote == '"' && c == '\\')
+                    .then(|| chars.next_if(|c| matches!(c, '"' | '`' | '$' | '\\')))
+                    .flatten();
+                part.push(escaped.unwrap_or(c));
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                part.push(c);
+            }
+        }
+
+        parts.push((part, quote.is_some()));
+    }
+
+    parts
+}
+
+/// Whether the arguments so far end in a shell and its `-c`, or combined flags like `-lc`
+fn is_shell_command(parts: &[(String, bool)]) -> bool {
+    let [.., (program, _), (flag, false)] = parts else {
+        return false;
+    };
+
+    let program = program.rsplit('/').next().unwrap_or(program);
+    let flags = flag.strip_prefix('-').unwrap_or_default();
+
+    SHELLS.contains(&program)
+        && flags.ends_with('c')
+        && flags.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+/// The arguments one part of an Exec line becomes
+fn expand_field_codes(part: &str, file_args: &[String]) -> Vec<String> {
+    // A code on its own takes one file or URL, or all of them for the plural codes
+    match part {
+        "%f" | "%u" => return file_args.first().cloned().into_iter().collect(),
+        "%F" | "%U" => return file_args.to_vec(),
+        _ => (),
+    }
+
+    let mut arg = String::new();
+    let mut had_code = false;
+    let mut chars = part.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            arg.push(c);
+        } else if chars.next_if_eq(&'%').is_some() {
+            arg.push('%');
+        } else {
+            if let (Some('f' | 'F' | 'u' | 'U'), Some(file)) = (chars.next(), file_args.first()) {
+                arg.push_str(file);
+            }
+            had_code = true;
+        }
+    }
+
+    if arg.is_empty() && had_code {
+        Vec::new()
+    } else {
+        vec![arg]
+    }
+}
+
+/// Whether a program name or path resolves to an executable file, like TryExec requires
+pub fn is_executable(program: &str) -> bool {
+    let executable = |path: &Path| {
+        path.metadata()
+            .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+    };
+
+    if program.contains('/') {
+        return executable(Path::new(program));
+    }
+
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| executable(&dir.join(program))))
+}
+
+/// Split leading `VAR=value` assignments, optionally after `env`, off a command
+///
+/// `env` is only unwrapped when nothing but assignments follow it, so options
+/// like `env -u VAR` keep running through `env` itself.
+pub fn split_env(parts: Vec<String>) -> (Vec<(String, String)>, Vec<String>) {
+    let skip = match parts.first().map(String::as_str) {
+        Some("env") if parts.get(1).is_some_and(|part| assignment(part).is_some()) => 1,
+        Some("env") => return (Vec::new(), parts),
+        _ => 0,
+    };
+
+    let mut vars = Vec::new();
+    let mut rest = parts.into_iter().skip(skip).peekable();
+
+    while let Some((name, value)) = rest.peek().and_then(|part| assignment(part)) {
+        vars.push((name.to_string(), value.to_string()));
+        rest.next();
+    }
+
+    (vars, rest.collect())
+}
+
+fn assignment(part: &str) -> Option<(&str, &str)> {
+    let (name, value) = part.split_once('=')?;
+    let mut chars = name.chars();
+
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    valid.then_some((name, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(exec: &str) -> (Vec<(String, String)>, Vec<String>) {
+        split_env(exec_args(exec, &[]))
+    }
+
+    fn var(name: &str, value: &str) -> (String, String) {
+        (name.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn bare_assignments_become_env() {
+        let (vars, args) = split("GDK_BACKEND=x11 QT_SCALE_FACTOR=1 app --flag %U");
+
+        assert_eq!(
+            vars,
+            [var("GDK_BACKEND", "x11"), var("QT_SCALE_FACTOR", "1")]
+        );
+        assert_eq!(args, ["app", "--flag"]);
+    }
+
+    #[test]
+    fn env_prefix_is_unwrapped() {
+        let (vars, args) = split("env VAR=val program args");
+
+        assert_eq!(vars, [var("VAR", "val")]);
+        assert_eq!(args, ["program", "args"]);
+    }
+
+    #[test]
+    fn env_with_options_is_kept() {
+        let (vars, args) = split("env -u VAR program");
+
+        assert!(vars.is_empty());
+        assert_eq!(args, ["env", "-u", "VAR", "program"]);
+    }
+
+    #[test]
+    fn arguments_with_equals_are_not_assignments() {
+        let (vars, args) = split("app --size=10 KEY=value");
+
+        assert!(vars.is_empty());
+        assert_eq!(args, ["app", "--size=10", "KEY=value"]);
+    }
+
+    #[test]
+    fn double_percent_is_a_literal_percent() {
+        let (_, args) = split("app --output ~/100%%/report.pdf %f");
+
+        assert_eq!(args, ["app", "--output", "~/100%/report.pdf"]);
+    }
+
+    #[test]
+    fn field_codes_inside_arguments_are_removed() {
+        let (_, args) = split("app --file=%f %%U");
+
+        assert_eq!(args, ["app", "--file=", "%U"]);
+    }
+
+    #[test]
+    fn file_codes_take_default_arguments() {
+        let defaults = [String::from("https://example.org"), String::from("b")];
+
+        assert_eq!(
+            exec_args("browser %u --flag", &defaults),
+            ["browser", "https://example.org", "--flag"]
+        );
+        assert_eq!(
+            exec_args("viewer %F", &defaults),
+            ["viewer", "https://example.org", "b"]
+        );
+    }
+
+    #[test]
+    fn shell_commands_stay_one_argument() {
+        assert_eq!(
+            exec_args(r#"sh -c "ps aux | grep -v \"grep\" > /tmp/%%s.log""#, &[]),
+            ["sh", "-c", r#"ps aux | grep -v "grep" > /tmp/%s.log"#]
+        );
+        assert_eq!(
+            exec_args("bash -lc 'cmd | other' %f", &[String::from("a b")]),
+            ["bash", "-lc", "cmd | other", "a b"]
+        );
+    }
+
+    #[test]
+    fn single_quotes_only_group_shell_commands() {
+        let (_, args) = split("app 'two words'");
+
+        assert_eq!(args, ["app", "'two", "words'"]);
+    }
+
+    #[test]
+    fn invalid_names_are_not_assignments() {
+        let (vars, args) = split("1VAR=x ./run.sh");
+
+        assert!(vars.is_empty());
+        assert_eq!(args, ["1VAR=x", "./run.sh"]);
+    }
+}
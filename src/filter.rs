@@ -0,0 +1,434 @@
+use crate::apps::{Application, Packaging};
+use crate::config::{Config, MatchMode, ShortQuery};
+use crate::frecency::Frecency;
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use regex::{Regex, RegexBuilder};
+use std::cmp::Ordering;
+
+/// Percentage of its score a transliterated name match counts for
+const TRANSLITERATION_WEIGHT: i64 = 75;
+
+/// Percentage of its score a desktop ID match counts for, below any name match
+const ID_WEIGHT: i64 = 50;
+
+/// Bonus per query character when the query spells out the starts of words in the name
+const ACRONYM_BONUS_PER_CHAR: i64 = 15;
+
+/// What Enter does to the focused result when the query ends in `:name`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultAction {
+    /// Run it in a terminal that stays open
+    Terminal,
+    /// Copy its command or URL
+    Copy,
+    /// Show its details
+    Info,
+}
+
+impl ResultAction {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "terminal" => Some(Self::Terminal),
+            "copy" => Some(Self::Copy),
+            "info" => Some(Self::Info),
+            _ => None,
+        }
+    }
+}
+
+/// Split a trailing `:action` off the query, leaving queries without a known one untouched
+pub fn split_action(search: &str) -> (&str, Option<ResultAction>) {
+    search
+        .rsplit_once(':')
+        .and_then(|(query, name)| ResultAction::from_name(name).map(|a| (query.trim_end(), a)))
+        .map_or((search, None), |(query, action)| (query, Some(action)))
+}
+
+/// Split a leading `src:flatpak` off the query, returning the label and the rest
+pub fn split_packaging(search: &str) -> (Option<&str>, &str) {
+    match search.strip_prefix("src:") {
+        Some(rest) => {
+            let (label, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            (Some(label), rest.trim_start())
+        }
+        None => (None, search),
+    }
+}
+
+/// Split `-term` exclusions off the query, returning the terms and the rest
+///
+/// Regex queries are left as they are, `-` is part of the pattern there.
+pub fn split_exclusions(search: &str, mode: MatchMode) -> (Vec<&str>, String) {
+    if search.starts_with("re:") || mode == MatchMode::Regex {
+        return (Vec::new(), search.to_string());
+    }
+
+    let (excluded, kept): (Vec<&str>, Vec<&str>) = search
+        .split_whitespace()
+        .partition(|token| token.len() > 1 && token.starts_with('-'));
+    if excluded.is_empty() {
+        return (excluded, search.to_string());
+    }
+
+    let excluded = excluded.into_iter().map(|token| &token[1..]).collect();
+    (excluded, kept.join(" "))
+}
+
+/// Whether the name, generic name or desktop ID contains one of the `excluded` terms
+fn is_excluded(application: &Application, excluded: &[String]) -> bool {
+    [
+        Some(&application.name),
+        application.generic_name.as_ref(),
+        Some(&application.id),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|field| field.to_lowercase())
+    .any(|field| excluded.iter().any(|term| field.contains(term)))
+}
+
+pub fn filter_applications(
+    applications: &[Application],
+    search: &str,
+    config: &Config,
+    frecency: &Frecency,
+) -> Result<Vec<Application>, String> {
+    let (search, _) = split_action(search);
+    let (packaging, search) = split_packaging(search);
+
+    if let Some(id) = search.strip_prefix("id:") {
+        return Ok(id_filter(applications, id.trim(), frecency));
+    }
+
+    let installed_as: Vec<Application>;
+    let applications = match packaging {
+        Some(label) => {
+            let packaging = Packaging::from_label(label)
+                .ok_or_else(|| format!("Unknown source {}, try flatpak, snap or native", label))?;
+            installed_as = applications
+                .iter()
+                .filter(|app| app.packaging == packaging)
+                .cloned()
+                .collect();
+            &installed_as[..]
+        }
+        None => applications,
+    };
+
+    let (excluded, search) = split_exclusions(search, config.match_mode);
+    let search = search.as_str();
+    let remaining: Vec<Application>;
+    let applications = if excluded.is_empty() {
+        applications
+    } else {
+        let excluded: Vec<String> = excluded.iter().map(|term| term.to_lowercase()).collect();
+        remaining = applications
+            .iter()
+            .filter(|app| !is_excluded(app, &excluded))
+            .cloned()
+            .collect();
+        &remaining[..]
+    };
+
+    // The `re:` prefix doesn't count towards the minimum length
+    let term = search.strip_prefix("re:").unwrap_or(search);
+    if term.chars().count() < config.min_query_len {
+        return Ok(match config.short_query {
+            ShortQuery::All => applications.to_vec(),
+            ShortQuery::None => Vec::new(),
+        });
+    }
+
+    if search.is_empty() {
+        return Ok(applications.to_vec());
+    }
+
+    if let Some(pattern) = search.strip_prefix("re:") {
+        return regex_filter(applications, pattern, frecency);
+    }
+
+    match config.match_mode {
+        MatchMode::Fuzzy => Ok(fuzzy_filter(applications, search, config, frecency)),
+        MatchMode::Regex => regex_filter(applications, search, frecency),
+        MatchMode::Substring => regex_filter(applications, &regex::escape(search), frecency),
+    }
+}
+
+fn fuzzy_filter(
+    applications: &[Application],
+    search: &str,
+    config: &Config,
+    frecency: &Frecency,
+) -> Vec<Application> {
+    let matcher = SkimMatcherV2::default();
+    let haystack = !config.haystack_fields.is_empty();
+    let mut matched_apps: Vec<(i64, &Application)> = applications
+        .iter()
+        .filter_map(|app| {
+            // A single matcher call per app, the fields were joined when loading
+            if haystack {
+                let score = matcher.fuzzy_match(app.haystack(), search)?;
+                let bonus = frecency.bonus(&app.id, config.frecency_weight);
+                return Some((score + app.score_boost(config) + bonus, app));
+            }
+
+            let transliterated_score = app
+                .transliterated_name
+                .as_ref()
+                .and_then(|name| matcher.fuzzy_match(name, search))
+                .map(|s| s * TRANSLITERATION_WEIGHT / 100);
+            let id_score = matcher
+                .fuzzy_match(&app.id, search)
+                .map(|s| s * ID_WEIGHT / 100);
+            let score = matcher
+                .fuzzy_match(&app.name, search)
+                .map(|s| normalize_length(s, &app.name, search, config.length_normalization))
+                .map(|s| s + acronym_bonus(&app.name, search))
+                .max(transliterated_score)
+                .max(id_score);
+
+            score.map(|s| {
+                let bonus = frecency.bonus(&app.id, config.frecency_weight);
+                (s + app.score_boost(config) + bonus, app)
+            })
+        })
+        .collect();
+
+    matched_apps.sort_by(|(a_score, a), (b_score, b)| {
+        b_score.cmp(a_score).then_with(|| tiebreak(a, b, frecency))
+    });
+
+    let best_score = matched_apps.first().map(|(score, _)| *score);
+    let mut filtered: Vec<Application> = matched_apps
+        .into_iter()
+        .map(|(_, app)| app.clone())
+        .collect();
+
+    if config.typo_tolerance && best_score.is_none_or(|s| s <= config.typo_score_threshold) {
+        let typo_matches = typo_filter(applications, search, frecency)
+            .into_iter()
+            .filter(|app| !filtered.iter().any(|f| f.id == app.id))
+            .cloned()
+            .collect::<Vec<_>>();
+        filtered.extend(typo_matches);
+    }
+
+    filtered
+}
+
+/// Scale a fuzzy score by how many times longer the name is than the query, raised to
+/// `exponent`
+fn normalize_length(score: i64, name: &str, search: &str, exponent: f64) -> i64 {
+    if exponent == 0.0 {
+        return score;
+    }
+
+    let ratio = name.chars().count() as f64 / search.chars().count().max(1) as f64;
+    (score as f64 * ratio.max(1.0).powf(exponent)).round() as i64
+}
+
+/// Favor "vsc" hitting "Visual Studio Code" over the letters scattered through a word
+fn acronym_bonus(name: &str, search: &str) -> i64 {
+    let query: Vec<char> = search
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if query.len() < 2 {
+        return 0;
+    }
+
+    let mut initials = word_starts(name);
+    let spelled = query.iter().all(|c| initials.any(|initial| initial == *c));
+
+    if spelled {
+        query.len() as i64 * ACRONYM_BONUS_PER_CHAR
+    } else {
+        0
+    }
+}
+
+/// First letters of each word, counting camel case humps like the "O" in "LibreOffice"
+fn word_starts(name: &str) -> impl Iterator<Item = char> + '_ {
+    let mut previous: Option<char> = None;
+
+    name.chars()
+        .filter(move |&c| {
+            let start = c.is_alphanumeric()
+                && previous
+                    .is_none_or(|p| !p.is_alphanumeric() || (p.is_lowercase() && c.is_uppercase()));
+            previous = Some(c);
+            start
+        })
+        .flat_map(char::to_lowercase)
+}
+
+/// Names within a few edits of the query, for typos skim's subsequence matching misses
+fn typo_filter<'a>(
+    applications: &'a [Application],
+    search: &str,
+    frecency: &Frecency,
+) -> Vec<&'a Application> {
+    let search = search.to_lowercase();
+    let max_distance = (search.chars().count() / 3).max(1);
+
+    let mut matched_apps: Vec<(usize, &Application)> = applications
+        .iter()
+        .filter_map(|app| {
+            let name = app.name.to_lowercase();
+
+            // Compare against each word too, so "fierox" can reach "Mozilla Firefox"
+            let distance = name
+                .split_whitespace()
+                .chain(std::iter::once(name.as_str()))
+                .map(|word| damerau_levenshtein(&search, word))
+                .min()?;
+
+            (distance <= max_distance).then_some((distance, app))
+        })
+        .collect();
+
+    matched_apps.sort_by(|(a_distance, a), (b_distance, b)| {
+        a_distance
+            .cmp(b_distance)
+            .then_with(|| tiebreak(a, b, frecency))
+    });
+
+    matched_apps.into_iter().map(|(_, app)| app).collect()
+}
+
+/// Order equally ranked results the same way on every keystroke: more used apps first,
+/// even when `frecency_weight` keeps usage out of the score, then shorter names, then
+/// alphabetically
+fn tiebreak(a: &Application, b: &Application, frecency: &Frecency) -> Ordering {
+    frecency
+        .score(&b.id)
+        .total_cmp(&frecency.score(&a.id))
+        .then_with(|| a.name.chars().count().cmp(&b.name.chars().count()))
+        .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+}
+
+/// Edit distance counting adjacent transpositions as a single edit
+fn damerau_levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+
+            distances[i][j] = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distances[i][j] = distances[i][j].min(distances[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    distances[a.len()][b.len()]
+}
+
+/// Keep apps whose desktop ID contains `id`, ignoring case, the exact ID first
+fn id_filter(applications: &[Application], id: &str, frecency: &Frecency) -> Vec<Application> {
+    let id = id.to_lowercase();
+
+    let mut matched_apps: Vec<(bool, usize, &Application)> = applications
+        .iter()
+        .filter_map(|app| {
+            let app_id = app.id.to_lowercase();
+            app_id
+                .find(&id)
+                .map(|position| (app_id != id, position, app))
+        })
+        .collect();
+
+    matched_apps.sort_by(|(a_inexact, a_position, a), (b_inexact, b_position, b)| {
+        (a_inexact, a_position)
+            .cmp(&(b_inexact, b_position))
+            .then_with(|| tiebreak(a, b, frecency))
+    });
+
+    matched_apps
+        .into_iter()
+        .map(|(_, _, app)| app.clone())
+        .collect()
+}
+
+/// Keep names matching the pattern, earliest match first
+fn regex_filter(
+    applications: &[Application],
+    pattern: &str,
+    frecency: &Frecency,
+) -> Result<Vec<Application>, String> {
+    let regex = smart_case_regex(pattern).map_err(|e| format!("Invalid regex: {}", e))?;
+
+    let mut matched_apps: Vec<(usize, &Application)> = applications
+        .iter()
+        .filter_map(|app| regex.find(&app.name).map(|m| (m.start(), app)))
+        .collect();
+
+    matched_apps.sort_by(|(a_position, a), (b_position, b)| {
+        a_position
+            .cmp(b_position)
+            .then_with(|| tiebreak(a, b, frecency))
+    });
+
+    Ok(matched_apps
+        .into_iter()
+        .map(|(_, app)| app.clone())
+        .collect())
+}
+
+/// Smart case like the fuzzy matcher: only case sensitive when the pattern has uppercase
+fn smart_case_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(!pattern.chars().any(char::is_uppercase))
+        .build()
+}
+
+/// Positions of the characters in `text` the query matched, for highlighting
+pub fn match_positions(text: &str, search: &str, mode: MatchMode) -> Vec<usize> {
+    let (search, _) = split_action(search);
+    let (_, search) = split_packaging(search);
+    let (_, search) = split_exclusions(search, mode);
+    let search = search.as_str();
+    if search.is_empty() || search.starts_with("id:") {
+        return Vec::new();
+    }
+
+    let pattern = match (search.strip_prefix("re:"), mode) {
+        (Some(pattern), _) => pattern.to_string(),
+        (None, MatchMode::Regex) => search.to_string(),
+        (None, MatchMode::Substring) => regex::escape(search),
+        (None, MatchMode::Fuzzy) => {
+            return SkimMatcherV2::default()
+                .fuzzy_indices(text, search)
+                .map(|(_, indices)| indices)
+                .unwrap_or_default();
+        }
+    };
+
+    let Some(found) = smart_case_regex(&pattern)
+        .ok()
+        .and_then(|regex| regex.find(text))
+    else {
+        return Vec::new();
+    };
+
+    text.char_indices()
+        .enumerate()
+        .filter(|(_, (byte, _))| found.range().contains(byte))
+        .map(|(i, _)| i)
+        .collect()
+}
@@ -0,0 +1,179 @@
+use crate::apps::Application;
+use crate::logger;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// How often and how recently each desktop ID was launched
+#[derive(Debug, Clone, Default)]
+pub struct Frecency {
+    launches: HashMap<String, Launches>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Launches {
+    count: u32,
+    /// Seconds since the Unix epoch
+    last_used: u64,
+}
+
+impl Frecency {
+    pub fn load() -> Self {
+        let launches = frecency_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|contents| contents.lines().filter_map(parse_line).collect())
+            .unwrap_or_default();
+
+        Self { launches }
+    }
+
+    /// Count a launch of `id` now and persist the counts
+    pub fn record(&mut self, id: &str) {
+        let launches = self.launches.entry(id.to_string()).or_insert(Launches {
+            count: 0,
+            last_used: 0,
+        });
+        launches.count += 1;
+        launches.last_used = now();
+
+        self.save();
+    }
+
+    /// Drop `id`'s launches, e.g. after it was launched by mistake
+    pub fn forget(&mut self, id: &str) {
+        if self.launches.remove(id).is_some() {
+            self.save();
+        }
+    }
+
+    /// Delete the persisted launch history altogether
+    pub fn reset() {
+        let Some(path) = frecency_path() else {
+            return;
+        };
+
+        match fs::remove_file(&path) {
+            Ok(()) => logger::info!("Removed {}", path.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                logger::info!("No launch history to remove")
+            }
+            Err(e) => logger::error!("Failed to remove {}: {}", path.display(), e),
+        }
+    }
+
+    /// The launched application used last, the first by name among launches in the
+    /// same second
+    pub fn most_recent<'a>(&self, applications: &'a [Application]) -> Option<&'a Application> {
+        applications
+            .iter()
+            .filter_map(|application| {
+                let launches = self.launches.get(&application.id)?;
+                Some((application, launches.last_used))
+            })
+            .min_by(|(a_app, a_used), (b_app, b_used)| {
+                b_used
+                    .cmp(a_used)
+                    .then_with(|| a_app.name.to_lowercase().cmp(&b_app.name.to_lowercase()))
+            })
+            .map(|(application, _)| application)
+    }
+
+    /// The `n` launched `applications` with the highest scores, best first
+    ///
+    /// Equal scores go to the most recently launched, then by name, so the order doesn't
+    /// shuffle between runs with the map's iteration order.
+    pub fn top<'a>(&self, applications: &'a [Application], n: usize) -> Vec<&'a Application> {
+        let mut launched: Vec<(&Application, f64, u64)> = applications
+            .iter()
+            .filter_map(|application| {
+                let launches = self.launches.get(&application.id)?;
+                Some((application, self.score(&application.id), launches.last_used))
+            })
+            .collect();
+        launched.sort_by(|(a_app, a, a_used), (b_app, b, b_used)| {
+            b.total_cmp(a)
+                .then_with(|| b_used.cmp(a_used))
+                .then_with(|| a_app.name.to_lowercase().cmp(&b_app.name.to_lowercase()))
+        });
+
+        launched
+            .into_iter()
+            .take(n)
+            .map(|(application, _, _)| application)
+            .collect()
+    }
+
+    /// How often `id` was launched and how many seconds ago it was last
+    pub fn usage(&self, id: &str) -> Option<(u32, u64)> {
+        self.launches
+            .get(id)
+            .map(|launches| (launches.count, now().saturating_sub(launches.last_used)))
+    }
+
+    /// Launch count weighted by how long ago the app was last used
+    pub fn score(&self, id: &str) -> f64 {
+        let Some((count, age)) = self.usage(id) else {
+            return 0.0;
+        };
+
+        f64::from(count) * recency(age)
+    }
+
+    /// Score bonus for `id`, logarithmic so heavy use can't bury better matches
+    pub fn bonus(&self, id: &str, weight: f64) -> i64 {
+        (self.score(id).ln_1p() * weight) as i64
+    }
+
+    fn save(&self) {
+        let Some(path) = frecency_path() else {
+            return;
+        };
+
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+
+        let contents: String = self
+            .launches
+            .iter()
+            .map(|(id, launches)| format!("{}\t{}\t{}\n", id, launches.count, launches.last_used))
+            .collect();
+
+        if let Err(e) = fs::write(&path, contents) {
+            logger::warn!("Failed to write {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// How much a launch `age` seconds ago counts, from 4 within a day down to 0.5
+pub fn recency(age: u64) -> f64 {
+    match age {
+        age if age < DAY => 4.0,
+        age if age < 7 * DAY => 2.0,
+        age if age < 30 * DAY => 1.0,
+        _ => 0.5,
+    }
+}
+
+fn parse_line(line: &str) -> Option<(String, Launches)> {
+    let mut fields = line.split('\t');
+    let id = fields.next()?.to_string();
+    let count = fields.next()?.parse().ok()?;
+    let last_used = fields.next()?.parse().ok()?;
+
+    Some((id, Launches { count, last_used }))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+fn frecency_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("astatine").join("frecency"))
+}
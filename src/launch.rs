@@ -0,0 +1,450 @@
+use crate::apps::{Application, Source};
+use crate::commands::CommandHistory;
+use crate::config::{Config, IoClass, LaunchMethod, LaunchPriority, TerminalConfig};
+use crate::exec;
+use crate::logger;
+use std::env;
+use std::fs;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Hold flags of emulators that can keep their window open themselves
+const KNOWN_HOLD_FLAGS: &[(&str, &str)] = &[
+    ("foot", "--hold"),
+    ("kitty", "--hold"),
+    ("alacritty", "--hold"),
+    ("konsole", "--hold"),
+    ("xterm", "-hold"),
+    ("urxvt", "-hold"),
+];
+
+/// Set once a launch started the countdown to closing
+static CLOSING: AtomicBool = AtomicBool::new(false);
+
+/// Launch an application and exit, wrapping it in a terminal when the entry asks for one
+/// or `hold` is set, in which case the terminal also stays open after the program exits
+pub fn execute_app_exec(application: &Application, config: &Config, hold: bool) {
+    if config.safe_mode {
+        let command = application.url.as_ref().unwrap_or(&application.exec);
+        logger::info!("Safe mode, not launching {}: {}", application.id, command);
+        return;
+    }
+
+    if is_duplicate_launch(&application.id, config) {
+        logger::info!("Ignoring a repeated launch of {}", application.id);
+        process::exit(0);
+    }
+
+    let prefix = config
+        .launch_prefix_for(&application.id)
+        .unwrap_or_default();
+
+    let priority = config.launch_priority_for(&application.id);
+
+    if !hold && prefix.is_empty() && priority.is_none() && launch_by_id(application, config) {
+        run_post_launch_hook(application, config);
+        return close(config);
+    }
+
+    // Without an Exec, D-Bus activation is the only way to start the app
+    if application.exec.is_empty() && application.dbus_activatable && application.url.is_none() {
+        if activate_over_dbus(&application.id) {
+            run_post_launch_hook(application, config);
+        } else {
+            logger::error!("Failed to activate {} over D-Bus", application.id);
+        }
+        return close(config);
+    }
+
+    // `GDK_BACKEND=x11 app` sets the variable rather than running `GDK_BACKEND=x11`
+    let (env_vars, exec_parts) = match &application.url {
+        Some(url) => (Vec::new(), vec![String::from("xdg-open"), url.clone()]),
+        None => {
+            let file_args: Vec<String> = config
+                .default_args
+                .get(&application.id)
+                .map(|args| args.split_whitespace().map(String::from).collect())
+                .unwrap_or_default();
+
+            exec::split_env(exec::exec_args(&application.exec, &file_args))
+        }
+    };
+    // The prefix wraps the expanded command, e.g. `firejail` or `flatpak run --branch=beta`
+    let mut parts: Vec<String> = prefix
+        .split_whitespace()
+        .map(String::from)
+        .chain(exec_parts)
+        .collect();
+
+    if application.terminal || hold {
+        parts = terminal_command(&config.terminal, parts, hold || config.terminal.hold);
+    }
+
+    if config.launch_method == LaunchMethod::SystemdScope {
+        if exec::is_executable("systemd-run") {
+            parts = scope_command(&application.id, parts);
+        } else {
+            logger::warn!(
+                "systemd-run not found, launching {} without a scope",
+                application.id
+            );
+        }
+    }
+
+    let mut parts = parts.into_iter();
+
+    if let Some(program) = parts.next() {
+        let args: Vec<String> = parts.collect();
+        logger::debug!("Launching {} {:?}", program, args);
+
+        let mut command = process::Command::new(&program);
+        command.args(args);
+        command.envs(env_vars);
+        if let Some(vars) = config.launch_env.get(&application.id) {
+            command.envs(vars);
+        }
+
+        let working_dir = application
+            .working_dir
+            .as_ref()
+            .or(config.default_working_dir.as_ref());
+        if let Some(dir) = working_dir {
+            command.current_dir(dir);
+        }
+        // Set in the child, so the terminal or scope and everything they start inherit it
+        if let Some(priority) = priority {
+            apply_priority(&mut command, priority);
+        }
+
+        match command.spawn() {
+            Ok(_) => run_post_launch_hook(application, config),
+            Err(e) => logger::error!("Failed to execute {}: {}", program, e),
+        }
+    } else {
+        logger::error!("No command provided.");
+    }
+
+    close(config);
+}
+
+/// Whether `id` was launched within the configured window, recording this launch
+///
+/// Kept on disk since every launch ends the process, so the repeat that matters comes
+/// from the next instance.
+fn is_duplicate_launch(id: &str, config: &Config) -> bool {
+    let window = Duration::from_millis(config.duplicate_launch_window_ms);
+    if window.is_zero() {
+        return false;
+    }
+
+    let Some(path) = last_launch_path() else {
+        return false;
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    let duplicate = fs::read_to_string(&path).ok().is_some_and(|contents| {
+        let (last_id, millis) = contents.trim_end().split_once('\t').unwrap_or_default();
+        let last = millis
+            .parse()
+            .map(Duration::from_millis)
+            .unwrap_or_default();
+
+        last_id == id && now.saturating_sub(last) < window
+    });
+
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Err(e) = fs::write(&path, format!("{}\t{}\n", id, now.as_millis())) {
+        logger::debug!("Failed to write {}: {}", path.display(), e);
+    }
+
+    duplicate
+}
+
+fn last_launch_path() -> Option<PathBuf> {
+    dirs::runtime_dir()
+        .or_else(dirs::cache_dir)
+        .map(|dir| dir.join("astatine").join("last-launch"))
+}
+
+/// Have the child take `priority` before it runs the app. Failures, like
+/// negative niceness without privileges, leave it at the default
+fn apply_priority(command: &mut process::Command, priority: LaunchPriority) {
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_SHIFT: u8 = 13;
+
+    let ioprio = priority.ionice.map(|class| {
+        // The middle of the 0-7 levels, which is what the kernel assumes without one
+        let level = if class == IoClass::Idle { 0 } else { 4 };
+        libc::c_int::from(class.number()) << IOPRIO_CLASS_SHIFT | level
+    });
+
+    // SAFETY: the closure only makes system calls, which is safe between fork and exec
+    unsafe {
+        command.pre_exec(move || {
+            if let Some(nice) = priority.nice {
+                libc::setpriority(libc::PRIO_PROCESS, 0, nice.into());
+            }
+            if let Some(ioprio) = ioprio {
+                libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio);
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Wrap `parts` in a transient systemd scope named like desktops name theirs,
+/// `app-<launcher>-<desktop ID>-<random>.scope`
+fn scope_command(id: &str, parts: Vec<String>) -> Vec<String> {
+    // Unit names allow only a few characters, and `-` separates the name's parts
+    let escaped: String = id
+        .bytes()
+        .map(|b| match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_' | b'.' | b':' => {
+                char::from(b).to_string()
+            }
+            b => format!("\\x{:02x}", b),
+        })
+        .collect();
+    let unique = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos()
+        ^ process::id();
+    let unit = format!("app-astatine-{}-{:x}.scope", escaped, unique);
+
+    ["systemd-run", "--user", "--scope", "--quiet", "--collect"]
+        .map(String::from)
+        .into_iter()
+        .chain([format!("--unit={}", unit)])
+        .chain(parts)
+        .collect()
+}
+
+/// Start a desktop entry through the configured launch method, false when it has to
+/// fall back to running the Exec
+fn launch_by_id(application: &Application, config: &Config) -> bool {
+    // Script lines, paths, config entries and Link entries have no desktop ID to hand over
+    let launchable = application.source == Source::Desktop
+        && application.url.is_none()
+        && !application.from_action;
+    if !launchable {
+        return false;
+    }
+
+    let id = &application.id;
+    let mut command = match config.launch_method {
+        LaunchMethod::Exec | LaunchMethod::SystemdScope => return false,
+        LaunchMethod::GtkLaunch => {
+            let mut command = process::Command::new("gtk-launch");
+            command.arg(id);
+            command
+        }
+        LaunchMethod::Dbus => activate_command(id),
+    };
+
+    if let Some(vars) = config.launch_env.get(id) {
+        command.envs(vars);
+    }
+    logger::debug!("Launching {} with {:?}", id, command);
+
+    // Waited on for D-Bus, whose failure only shows in the exit status
+    let launched = match config.launch_method {
+        LaunchMethod::Dbus => command.status().map(|status| status.success()),
+        _ => command.spawn().map(|_| true),
+    };
+
+    match launched {
+        Ok(true) => true,
+        Ok(false) => {
+            logger::warn!("Activating {} over D-Bus failed, running its Exec", id);
+            false
+        }
+        Err(e) => {
+            logger::warn!(
+                "Failed to launch {} with {:?}: {}, running its Exec",
+                id,
+                config.launch_method,
+                e
+            );
+            false
+        }
+    }
+}
+
+/// Ask a D-Bus activatable app to activate itself, starting it or raising its window
+pub fn activate_over_dbus(id: &str) -> bool {
+    logger::debug!("Activating {} over D-Bus", id);
+
+    activate_command(id)
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// The `org.freedesktop.Application.Activate` call for a desktop ID
+fn activate_command(id: &str) -> process::Command {
+    // The object path is the bus name with `.` as `/` and `-` as `_`
+    let object_path = format!("/{}", id.replace('.', "/").replace('-', "_"));
+    let mut command = process::Command::new("gdbus");
+    command.args(["call", "--session", "--dest", id, "--object-path"]);
+    command.args([
+        &object_path,
+        "--method",
+        "org.freedesktop.Application.Activate",
+    ]);
+    command.arg("{}");
+    command
+}
+
+/// Run the configured hook for a launched app, without letting its failure matter
+pub fn run_post_launch_hook(application: &Application, config: &Config) {
+    let Some(hook) = &config.post_launch_hook else {
+        return;
+    };
+
+    let spawned = process::Command::new("sh")
+        .arg("-c")
+        .arg(hook)
+        .env("ASTATINE_ID", &application.id)
+        .env("ASTATINE_NAME", &application.name)
+        .env("ASTATINE_EXEC", &application.exec)
+        .spawn();
+
+    if let Err(e) = spawned {
+        logger::warn!("Failed to run post launch hook: {}", e);
+    }
+}
+
+/// Run a `>` mode command through the shell and remember it
+pub fn execute_command(command: &str, history: &mut CommandHistory, config: &Config) {
+    let command = command.trim();
+    if command.is_empty() {
+        return;
+    }
+
+    if config.safe_mode {
+        logger::info!("Safe mode, not running {}", command);
+        return;
+    }
+
+    history.record(command);
+
+    let mut shell = process::Command::new("sh");
+    shell.arg("-c").arg(command);
+
+    if let Some(dir) = &config.default_working_dir {
+        shell.current_dir(dir);
+    }
+
+    if let Err(e) = shell.spawn() {
+        logger::error!("Failed to execute {}: {}", command, e);
+    }
+
+    close(config);
+}
+
+/// Open the configured terminal emulator with `dir` as its working directory and exit
+pub fn open_terminal(dir: &Path, config: &Config) {
+    if config.safe_mode {
+        logger::info!("Safe mode, not opening a terminal in {}", dir.display());
+        return;
+    }
+
+    let mut parts = emulator(&config.terminal).into_iter();
+
+    if let Some(program) = parts.next() {
+        let spawned = process::Command::new(&program)
+            .args(parts)
+            .current_dir(dir)
+            .spawn();
+
+        if let Err(e) = spawned {
+            logger::error!("Failed to execute {}: {}", program, e);
+        }
+    }
+
+    close(config);
+}
+
+/// Exit after the configured `close_delay_ms`, right away when it's 0
+///
+/// With a delay this returns and the exit follows from another thread, leaving the
+/// window up meanwhile so the compositor can move focus to the launched app.
+pub fn close(config: &Config) {
+    let delay = Duration::from_millis(config.close_delay_ms);
+    if delay.is_zero() {
+        process::exit(0);
+    }
+
+    if !CLOSING.swap(true, Ordering::Relaxed) {
+        thread::spawn(move || {
+            thread::sleep(delay);
+            process::exit(0);
+        });
+    }
+}
+
+/// Whether a launch already happened and the window is only waiting to close
+pub fn closing() -> bool {
+    CLOSING.load(Ordering::Relaxed)
+}
+
+/// The terminal emulator command, from config, then `$TERMINAL`, then `xterm`
+fn emulator(terminal: &TerminalConfig) -> Vec<String> {
+    terminal
+        .command
+        .clone()
+        .or_else(|| env::var("TERMINAL").ok())
+        .unwrap_or_else(|| String::from("xterm"))
+        .split_whitespace()
+        .map(String::from)
+        .collect()
+}
+
+/// Wrap `parts` in an invocation of the configured terminal emulator
+fn terminal_command(terminal: &TerminalConfig, parts: Vec<String>, hold: bool) -> Vec<String> {
+    let mut command = emulator(terminal);
+    let program = command.first().cloned().unwrap_or_default();
+
+    let hold_flag = terminal.hold_flag.clone().or_else(|| {
+        KNOWN_HOLD_FLAGS
+            .iter()
+            .find(|(name, _)| program.rsplit('/').next() == Some(*name))
+            .map(|(_, flag)| flag.to_string())
+    });
+
+    match (hold, hold_flag) {
+        (true, Some(flag)) => {
+            command.push(flag);
+            command.push(terminal.exec_flag.clone());
+            command.extend(parts);
+        }
+        (true, None) => {
+            // No hold flag, so wait for a key in the shell before the terminal closes
+            command.push(terminal.exec_flag.clone());
+            command.extend(
+                [
+                    "sh",
+                    "-c",
+                    r#""$@"; printf '\n[Process exited, press Enter to close]'; read -r _"#,
+                    "sh",
+                ]
+                .map(String::from),
+            );
+            command.extend(parts);
+        }
+        (false, _) => {
+            command.push(terminal.exec_flag.clone());
+            command.extend(parts);
+        }
+    }
+
+    command
+}
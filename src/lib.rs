@@ -0,0 +1,24 @@
+pub mod accent;
+pub mod apps;
+#[cfg(feature = "blur")]
+pub mod blur;
+pub mod cache;
+pub mod cli;
+pub mod commands;
+pub mod config;
+#[cfg(feature = "converter")]
+pub mod converter;
+pub mod exec;
+pub mod filter;
+pub mod frecency;
+pub mod launch;
+pub mod logger;
+pub mod paths;
+pub mod scripts;
+pub mod session;
+pub mod template;
+#[cfg(feature = "transliteration")]
+pub mod transliterate;
+pub mod validate;
+#[cfg(feature = "window-count")]
+pub mod windows;
@@ -1,28 +1,136 @@
-use freedesktop_desktop_entry::{Iter, default_paths, get_languages_from_env};
-use freedesktop_icons::lookup;
-use fuzzy_matcher::FuzzyMatcher;
-use fuzzy_matcher::skim::SkimMatcherV2;
+use astatine::accent::dominant_color;
+#[cfg(feature = "json")]
+use astatine::apps::resolve_icon;
+use astatine::apps::{
+    Application, Icon, Packaging, Source, build_applications, get_applications,
+    resolve_icon_within, user_entry_path,
+};
+#[cfg(feature = "blur")]
+use astatine::blur;
+use astatine::cli::Args;
+use astatine::commands::CommandHistory;
+use astatine::config::{ClickMode, Config, IconPlaceholder, Layout, MatchMode};
+#[cfg(feature = "converter")]
+use astatine::converter;
+use astatine::filter::{ResultAction, filter_applications, match_positions, split_action};
+use astatine::frecency::{Frecency, recency};
+use astatine::launch::{self, closing, execute_app_exec, execute_command, open_terminal};
+use astatine::session::Session;
+use astatine::template::Template;
+#[cfg(feature = "window-count")]
+use astatine::windows::{self, WindowCounts};
+use astatine::{logger, paths, scripts, validate};
+use freedesktop_desktop_entry::{DesktopEntry, default_paths};
+use iced::futures::channel::oneshot;
 use iced::{
-    Background, Color, ContentFit, Padding, Size, Subscription, Task, Theme, keyboard,
-    widget::{button, column, container, image, row, svg, text, text_input},
+    Background, Color, ContentFit, Padding, Size, Subscription, Task, Theme, application,
+    clipboard, keyboard, task,
+    widget::{
+        Space, button, column, container, image, rich_text, row, span, svg, text, text_input,
+    },
+    window,
 };
-use icon_loader::IconLoader;
-use std::collections::HashSet;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
 struct Astatine {
+    config: Config,
     search: String,
-    applications: Vec<Application>,
-    matcher: SkimMatcherV2,
+    applications: Arc<Vec<Application>>,
+    filtered: Vec<Application>,
+    /// A unit or currency conversion of the query, shown above the applications
+    conversion: Option<String>,
+    /// Why the current search couldn't be matched, e.g. an invalid regex
+    search_error: Option<String>,
+    /// The in-flight filter computation, aborted when superseded
+    filter_task: Option<task::Handle>,
+    /// The running `filter_command`, killed when a newer query replaces it
+    filter_process: Option<scripts::FilterProcess>,
+    /// The wait before `filter_command` runs, aborted by the next keystroke
+    filter_debounce: Option<task::Handle>,
+    /// Bumped with every query, so a debounced or running `filter_command` knows it's stale
+    filter_generation: u64,
+    command_history: CommandHistory,
+    frecency: Arc<Frecency>,
+    /// Icons resolved so far by desktop ID, `None` while loading and `Icon::None` when
+    /// not found
+    icons: HashMap<String, Option<Icon>>,
+    /// Colors sampled from resolved icons by desktop ID, with `accent_colors` on
+    accents: HashMap<String, Color>,
+    /// Desktop IDs and icon names waiting for one of the `icon_concurrency` lookups
+    icon_queue: VecDeque<(String, String)>,
+    /// Icon lookups running right now
+    icons_loading: usize,
+    /// Past commands matching the query while in `>` command mode
+    command_suggestions: Vec<String>,
+    /// How far back Up has walked through the command history
+    history_cursor: Option<usize>,
+    /// The script whose trigger the query starts with
+    active_script: Option<usize>,
+    /// Lines output by the active script, `None` while it runs
+    script_results: Option<Arc<Vec<Application>>>,
     focus: usize,
     prev_focus: Option<usize>,
+    /// Show the focused application's details instead of the result list
+    show_detail: bool,
+    /// Index of the focused action while the focused app's Desktop Actions are expanded
+    open_actions: Option<usize>,
+    /// The focus before it last moved and when, while the highlight fades over
+    focus_moved: Option<(usize, Instant)>,
+    /// A user `.desktop` file waiting for `y` to be deleted
+    confirm_removal: Option<PathBuf>,
+    /// Parsed `result_format`
+    result_template: Template,
+    /// Strict substring matching toggled on for this session, instead of `match_mode`
+    substring_match: bool,
+    /// Only `favorites` are matched, toggled with Ctrl+F
+    favorites_only: bool,
+    /// Main categories any application belongs to, in `MAIN_CATEGORIES` order
+    categories: Vec<&'static str>,
+    /// The chip the results are restricted to
+    category: Option<&'static str>,
+    /// The result last clicked and when, to tell a double click from two single ones
+    last_click: Option<(usize, Instant)>,
+    /// Modifiers held right now, Alt forcing a new instance over `raise_existing`
+    modifiers: keyboard::Modifiers,
+    /// `--theme-preview`: sample results that can't be launched
+    preview: bool,
+    /// Windows open per app when the launcher started, shown as badges on results
+    #[cfg(feature = "window-count")]
+    window_counts: WindowCounts,
+    /// `--windows`: open windows listed instead of apps, reached through this session
+    #[cfg(feature = "window-count")]
+    window_switcher: Option<Session>,
+    loading: bool,
 }
 
 #[derive(Debug, Clone)]
 enum Message {
     SearchSubmit,
     SearchChanged(String),
+    SearchPasted(String),
     KeyPressed(String),
+    AppsLoaded(Vec<Application>),
+    FilterComputed(Result<Vec<Application>, String>),
+    ScriptLoaded(usize, Vec<Application>),
+    FilterCommandDue(u64),
+    FilterCommandDone(u64, Vec<Application>),
+    ConfigReloaded(Box<Config>),
+    IconResolved(String, Option<Icon>, Option<Color>),
+    ResultClicked(usize),
+    QuickLaunch(usize),
+    CategorySelected(Option<&'static str>),
+    ModifiersChanged(keyboard::Modifiers),
+    AnimationFrame(Instant),
+    #[cfg(feature = "window-count")]
+    WindowsCounted(WindowCounts),
 }
 
 trait MessageProcessor<T> {
@@ -35,18 +143,299 @@ impl MessageProcessor<String> for SearchChangedProcessor {
         state.search = param;
         state.prev_focus = None;
         state.focus = 0;
+        state.history_cursor = None;
+        state.open_actions = None;
+        // Command mode and scripts don't reach `filter_applications()` right away
+        state.cancel_filter_command();
 
-        Task::none()
+        if let Some(command) = state.command_query() {
+            state.command_suggestions = state.command_history.suggestions(command);
+            return Task::none();
+        }
+
+        let active_script = state
+            .config
+            .scripts
+            .iter()
+            .position(|script| state.search.starts_with(&script.trigger));
+
+        if active_script != state.active_script {
+            state.active_script = active_script;
+            state.script_results = None;
+            state.filtered.clear();
+            // Results for the previous candidate set are no longer wanted
+            state.filter_task = None;
+
+            if let Some(index) = active_script {
+                let script = state.config.scripts[index].clone();
+
+                return Task::perform(async move { scripts::run_script(&script) }, move |lines| {
+                    Message::ScriptLoaded(index, lines)
+                });
+            }
+        }
+
+        #[cfg(feature = "converter")]
+        {
+            state.conversion = converter::convert(&state.search);
+        }
+
+        state.filter_applications()
+    }
+}
+
+struct ScriptLoadedProcessor;
+impl MessageProcessor<(usize, Vec<Application>)> for ScriptLoadedProcessor {
+    fn process(state: &mut Astatine, (index, lines): (usize, Vec<Application>)) -> Task<Message> {
+        // The trigger may have been deleted while the script ran
+        if state.active_script != Some(index) {
+            return Task::none();
+        }
+
+        state.script_results = Some(Arc::new(lines));
+        state.filter_applications()
+    }
+}
+
+struct FilterCommandDueProcessor;
+impl MessageProcessor<u64> for FilterCommandDueProcessor {
+    fn process(state: &mut Astatine, param: u64) -> Task<Message> {
+        // Typing went on during the debounce
+        if param != state.filter_generation {
+            return Task::none();
+        }
+        state.filter_debounce = None;
+
+        let Some(command) = state.config.filter_command.clone() else {
+            return Task::none();
+        };
+
+        let (process, stdout) = match scripts::spawn_filter(&command, split_action(&state.search).0)
+        {
+            Ok(spawned) => spawned,
+            Err(e) => {
+                let error = format!("Failed to run {}: {}", command, e);
+                return FilterComputedProcessor::process(state, Err(error));
+            }
+        };
+        state.filter_process = Some(process);
+
+        let applications = Arc::clone(&state.applications);
+        let category = state.category;
+        let favorites = state.favorites_only.then(|| state.config.clone());
+
+        Task::perform(
+            async move {
+                let mut results = scripts::filter_results(stdout, &applications);
+                if let Some(category) = category {
+                    results
+                        .retain(|application| application.categories.iter().any(|c| c == category));
+                }
+                if let Some(config) = favorites {
+                    results.retain(|application| config.is_favorite(&application.id));
+                }
+                results
+            },
+            move |results| Message::FilterCommandDone(param, results),
+        )
+    }
+}
+
+struct FilterCommandDoneProcessor;
+impl MessageProcessor<(u64, Vec<Application>)> for FilterCommandDoneProcessor {
+    fn process(
+        state: &mut Astatine,
+        (generation, results): (u64, Vec<Application>),
+    ) -> Task<Message> {
+        // Output of a run killed for a newer query
+        if generation != state.filter_generation {
+            return Task::none();
+        }
+
+        state.filter_process = None;
+        FilterComputedProcessor::process(state, Ok(results))
+    }
+}
+
+struct SearchPastedProcessor;
+impl MessageProcessor<String> for SearchPastedProcessor {
+    fn process(state: &mut Astatine, param: String) -> Task<Message> {
+        // Pasted text arrives as a single value; drop whitespace picked up from the source
+        SearchChangedProcessor::process(state, param.trim().to_string())
     }
 }
 
 struct KeyPressedProcessor;
 impl MessageProcessor<String> for KeyPressedProcessor {
     fn process(state: &mut Astatine, param: String) -> Task<Message> {
+        // Nothing to navigate or launch until the applications arrive
+        if state.loading && param != "q" {
+            return Task::none();
+        }
+
+        // Sample results only show what launching would look like
+        if state.preview && param.ends_with("enter>") {
+            return Task::none();
+        }
+
+        if state.show_detail {
+            match param.as_str() {
+                "q" => process::exit(0),
+                "<space>" | "<C-i>" | "<esc>" => state.show_detail = false,
+                _ => (),
+            }
+
+            return Task::none();
+        }
+
+        if let Some(path) = state.confirm_removal.take() {
+            if param != "y" {
+                return Task::none();
+            }
+
+            match fs::remove_file(&path) {
+                Ok(()) => logger::info!("Removed {}", path.display()),
+                Err(e) => logger::error!("Failed to remove {}: {}", path.display(), e),
+            }
+            state.focus = state.focus.min(state.filtered.len());
+
+            return state.load_applications();
+        }
+
+        if let Some(action) = state.open_actions {
+            let count = state
+                .focused_application()
+                .map_or(0, |application| application.actions.len());
+
+            match param.as_str() {
+                "q" => process::exit(0),
+                "j" | "<down>" => {
+                    state.open_actions = Some((action + 1).min(count.saturating_sub(1)))
+                }
+                "k" | "<up>" => state.open_actions = Some(action.saturating_sub(1)),
+                "<left>" | "<esc>" => state.open_actions = None,
+                "<enter>" => {
+                    if let Some(application) = state.focused_application().cloned() {
+                        state.record_launch(&application.id);
+                        execute_app_exec(&application.with_action(action), &state.config, false);
+                    }
+                }
+                _ => (),
+            }
+
+            return Task::none();
+        }
+
+        if let Some(slot) = quick_launch_slot(&param).filter(|_| state.config.quick_launch > 0) {
+            return QuickLaunchProcessor::process(state, slot);
+        }
+
         match param.as_str() {
             "q" => {
                 process::exit(0);
             }
+            "<esc>" => {
+                // From the results start over in an empty search box, from there close
+                if state.focus == 0 {
+                    process::exit(0);
+                }
+
+                let clear = SearchChangedProcessor::process(state, String::new());
+                return Task::batch([clear, state.focus_search()]);
+            }
+            "<C-r>" => {
+                return Task::perform(
+                    async {
+                        let mut config = Config::load();
+                        config.apply_args(&Args::parse());
+                        Box::new(config)
+                    },
+                    Message::ConfigReloaded,
+                );
+            }
+            "<C-d>" => {
+                // Forget the focused app's launches so it stops ranking higher
+                if let Some(id) = state.focused_application().map(|app| app.id.clone()) {
+                    Arc::make_mut(&mut state.frecency).forget(&id);
+                    return state.filter_applications();
+                }
+            }
+            "<delete>" => {
+                // Only the user's own entries, and only after confirming with `y`
+                let path = state
+                    .focused_application()
+                    .and_then(user_entry_path)
+                    .map(Path::to_path_buf);
+
+                match path {
+                    Some(_) if state.config.safe_mode => {
+                        logger::info!("Safe mode, not removing entries");
+                    }
+                    Some(path) => state.confirm_removal = Some(path),
+                    None => {
+                        logger::info!("Only entries in ~/.local/share/applications can be removed")
+                    }
+                }
+            }
+            "<C-s>" => {
+                state.substring_match = !state.substring_match;
+                return state.filter_applications();
+            }
+            "<C-f>" => {
+                if state.config.favorites.is_empty() {
+                    logger::info!("No favorites to show, list them in the config's favorites");
+                    return Task::none();
+                }
+
+                state.favorites_only = !state.favorites_only;
+                return state.filter_applications();
+            }
+            "<C-left>" | "<C-right>" if state.config.category_chips => {
+                // No category sits before the first chip and after the last
+                let count = state.categories.len() + 1;
+                let current = state
+                    .category
+                    .and_then(|category| state.categories.iter().position(|c| *c == category))
+                    .map_or(0, |i| i + 1);
+                let next = if param == "<C-right>" {
+                    (current + 1) % count
+                } else {
+                    (current + count - 1) % count
+                };
+
+                let category = next.checked_sub(1).map(|i| state.categories[i]);
+                return CategorySelectedProcessor::process(state, category);
+            }
+            "<space>" | "<C-i>" => {
+                state.show_detail = state.focused_application().is_some();
+            }
+            "<right>" => {
+                let has_actions = state
+                    .focused_application()
+                    .is_some_and(|application| !application.actions.is_empty());
+                if has_actions && state.active_script.is_none() && state.config.columns == 1 {
+                    state.open_actions = Some(0);
+                }
+            }
+            "j" | "k" | "h" | "l" if state.config.columns > 1 => {
+                if let Some(prev_focus) = state.prev_focus {
+                    state.focus = prev_focus;
+                    state.prev_focus = None;
+                }
+
+                let columns = state.config.columns;
+                let first = state.leading_results() + 1;
+                let last = state.leading_results() + state.filtered.len();
+                state.focus = match param.as_str() {
+                    "j" if state.focus < first => state.focus + 1,
+                    "j" => (state.focus + columns).min(last),
+                    "k" if state.focus >= first + columns => state.focus - columns,
+                    // Up from the first row leaves the grid for the row or search box above
+                    "k" => state.focus.min(first) - 1,
+                    "h" => state.focus.saturating_sub(1).max(first.min(state.focus)),
+                    _ => (state.focus + 1).min(last.max(state.focus)),
+                };
+            }
             "j" => {
                 if let Some(prev_focus) = state.prev_focus {
                     state.focus = prev_focus;
@@ -65,41 +454,106 @@ impl MessageProcessor<String> for KeyPressedProcessor {
                 state.prev_focus = Some(state.focus);
                 state.focus = 0;
             }
+            "<up>" | "<down>" if state.command_query().is_some() => {
+                let older = param == "<up>";
+                state.history_cursor = match state.history_cursor {
+                    None if older => (!state.command_history.is_empty()).then_some(0),
+                    Some(n) if older => Some((n + 1).min(state.command_history.len() - 1)),
+                    Some(n) => n.checked_sub(1),
+                    None => None,
+                };
+
+                let command = state
+                    .history_cursor
+                    .and_then(|n| state.command_history.recent(n))
+                    .unwrap_or_default();
+                state.search = format!(">{}", command);
+
+                return text_input::move_cursor_to_end("search");
+            }
+            "<down>" | "<tab>" if state.focus == 0 => {
+                // The search box lets these through, the way into the results by keyboard
+                state.focus = 1;
+                return text_input::focus("<none>");
+            }
             "<enter>" => {
-                let filtered_applications = if state.search.is_empty() {
-                    state.applications.clone()
-                } else {
-                    let mut matched_apps: Vec<(i64, Application)> = state
-                        .applications
-                        .iter()
-                        .filter_map(|app| {
-                            let score = state.matcher.fuzzy_match(&app.name, &state.search);
+                if state.command_query().is_some() {
+                    let Some(command) = state
+                        .focus
+                        .checked_sub(1)
+                        .and_then(|i| state.command_suggestions.get(i))
+                        .cloned()
+                    else {
+                        return Task::none();
+                    };
 
-                            score.map(|s| (s, app.clone()))
-                        })
-                        .collect();
+                    execute_command(&command, &mut state.command_history, &state.config);
+                    if closing() || state.config.safe_mode {
+                        return Task::none();
+                    }
+                }
 
-                    matched_apps.sort_by(|a, b| b.0.cmp(&a.0));
+                if let (Some(conversion), 1) = (&state.conversion, state.focus) {
+                    return clipboard::write(conversion.clone());
+                }
 
-                    matched_apps.into_iter().map(|(_, app)| app).collect()
+                let Some(application) = state.focused_application().cloned() else {
+                    return Task::none();
                 };
 
-                let exec = filtered_applications
-                    .iter()
-                    .enumerate()
-                    .find(|(i, _)| i + 1 == state.focus)
-                    .unwrap()
-                    .1
-                    .exec
-                    .clone();
+                // A trailing `:terminal`, `:copy` or `:info` acts on the result instead
+                match split_action(&state.search).1 {
+                    Some(ResultAction::Terminal) if state.active_script.is_none() => {
+                        state.record_launch(&application.id);
+                        execute_app_exec(&application, &state.config, true);
+                        return Task::none();
+                    }
+                    Some(ResultAction::Copy) => {
+                        let copied = application.url.unwrap_or(application.exec);
+                        return clipboard::write(copied);
+                    }
+                    Some(ResultAction::Info) => {
+                        state.show_detail = true;
+                        return Task::none();
+                    }
+                    _ => (),
+                }
+
+                if let Some(index) = state.active_script {
+                    scripts::select(&state.config.scripts[index], &application, &state.config);
+                    return Task::none();
+                }
+
+                state.launch(&application);
+            }
+            "<S-enter>" => {
+                // Run in a terminal that stays open, for tools whose output should be read
+                if let Some(application) = state
+                    .focused_application()
+                    .filter(|_| state.active_script.is_none())
+                    .cloned()
+                {
+                    state.record_launch(&application.id);
+                    execute_app_exec(&application, &state.config, true);
+                }
+            }
+            "<C-enter>" => {
+                // Directories from a path query get a shell opened inside them
+                let dir = state
+                    .focused_application()
+                    .and_then(|application| application.url.as_deref())
+                    .map(Path::new)
+                    .filter(|path| path.is_dir());
 
-                execute_app_exec(exec);
+                if let Some(dir) = dir {
+                    open_terminal(dir, &state.config);
+                }
             }
             _ => (),
         };
 
         if state.focus == 0 {
-            return focus_search();
+            return state.focus_search();
         }
 
         Task::none()
@@ -109,227 +563,1758 @@ impl MessageProcessor<String> for KeyPressedProcessor {
 struct SearchSubmitProcessor;
 impl MessageProcessor<()> for SearchSubmitProcessor {
     fn process(state: &mut Astatine, _: ()) -> Task<Message> {
-        state.focus = 1;
+        if state.loading || state.preview {
+            return Task::none();
+        }
+
+        // The search box submits whatever modifiers are held, so those keys go to the
+        // focused or top row here rather than as a separate key press
+        let key = if state.modifiers.shift() {
+            Some("<S-enter>")
+        } else if state.modifiers.control() {
+            Some("<C-enter>")
+        } else {
+            None
+        };
+        if let Some(key) = key {
+            state.focus = state.focus.max(1);
+            let key = KeyPressedProcessor::process(state, String::from(key));
+            return Task::batch([key, text_input::focus("<none>")]);
+        }
+
+        // Precedence: a mode's own action first, so command mode runs the typed command
+        if let Some(command) = state.command_query().map(str::to_string) {
+            execute_command(&command, &mut state.command_history, &state.config);
+            if closing() || state.config.safe_mode {
+                return Task::none();
+            }
+        }
+
+        // Keep a row already selected by `focus_last_used`, otherwise take the top one
+        state.focus = state.focus.max(1);
+
+        // Then that row as if it were focused, which copies a conversion shown above
+        // the applications before any of them would launch
+        if state.config.enter_launches_top {
+            let launch = KeyPressedProcessor::process(state, String::from("<enter>"));
+            return Task::batch([launch, text_input::focus("<none>")]);
+        }
 
         text_input::focus("<none>")
     }
 }
 
-impl Astatine {
-    fn new() -> Self {
-        Self {
-            search: String::from(""),
-            applications: get_applications(),
-            matcher: SkimMatcherV2::default(),
-            focus: 0,
-            prev_focus: None,
-        }
+struct AppsLoadedProcessor;
+impl MessageProcessor<Vec<Application>> for AppsLoadedProcessor {
+    fn process(state: &mut Astatine, param: Vec<Application>) -> Task<Message> {
+        state.categories = MAIN_CATEGORIES
+            .iter()
+            .map(|(category, _)| *category)
+            .filter(|category| {
+                param
+                    .iter()
+                    .any(|application| application.categories.iter().any(|c| c == category))
+            })
+            .collect();
+        state.applications = Arc::new(param);
+        state.loading = false;
+
+        // Keep whatever was typed while loading
+        state.filter_applications()
     }
+}
 
-    fn update(&mut self, message: Message) -> iced::Task<Message> {
-        match message {
-            Message::SearchSubmit => SearchSubmitProcessor::process(self, ()),
-            Message::SearchChanged(param) => SearchChangedProcessor::process(self, param),
-            Message::KeyPressed(param) => KeyPressedProcessor::process(self, param),
+struct ConfigReloadedProcessor;
+impl MessageProcessor<Box<Config>> for ConfigReloadedProcessor {
+    fn process(state: &mut Astatine, param: Box<Config>) -> Task<Message> {
+        logger::set_level(param.log_level);
+        logger::info!("Reloaded config");
+
+        // Colors and keys read the config on every use, the app list has to be rebuilt
+        state.config = *param;
+        state.result_template = Template::parse_or_default(&state.config.result_format);
+        // Icons were resolved for the old `icon_size`, and maybe without accents
+        state.icons.clear();
+        state.accents.clear();
+        let reload = state.load_applications();
+
+        // Scripts may have changed, so detect the active one again
+        state.active_script = None;
+        state.script_results = None;
+        let search = state.search.clone();
+
+        Task::batch([reload, SearchChangedProcessor::process(state, search)])
+    }
+}
+
+struct FilterComputedProcessor;
+impl MessageProcessor<Result<Vec<Application>, String>> for FilterComputedProcessor {
+    fn process(state: &mut Astatine, param: Result<Vec<Application>, String>) -> Task<Message> {
+        match param {
+            Ok(filtered) => {
+                state.filtered = filtered;
+                state.search_error = None;
+            }
+            Err(e) => {
+                state.filtered.clear();
+                state.search_error = Some(e);
+            }
+        }
+        state.filter_task = None;
+
+        if state.config.focus_last_used && state.search.is_empty() && state.focus == 0 {
+            let last_used = state
+                .frecency
+                .most_recent(&state.applications)
+                .and_then(|last| {
+                    state
+                        .filtered
+                        .iter()
+                        .position(|application| application.id == last.id)
+                });
+            if let Some(i) = last_used {
+                state.focus = i + 1 + state.leading_results();
+            }
         }
+
+        state.request_icons()
     }
+}
 
-    fn view(&self) -> iced::Element<'_, Message> {
-        let filtered_applications = if self.search.is_empty() {
-            self.applications.clone()
-        } else {
-            let mut matched_apps: Vec<(i64, Application)> = self
-                .applications
-                .iter()
-                .filter_map(|app| {
-                    let score = self.matcher.fuzzy_match(&app.name, &self.search);
+struct ResultClickedProcessor;
+impl MessageProcessor<usize> for ResultClickedProcessor {
+    fn process(state: &mut Astatine, param: usize) -> Task<Message> {
+        let double = state
+            .last_click
+            .is_some_and(|(focus, at)| focus == param && at.elapsed() < DOUBLE_CLICK_INTERVAL);
 
-                    score.map(|s| (s, app.clone()))
-                })
-                .collect();
+        state.focus = param;
+        state.prev_focus = None;
+        state.open_actions = None;
+        state.last_click = Some((param, Instant::now()));
 
-            matched_apps.sort_by(|a, b| b.0.cmp(&a.0));
+        if state.config.click_mode == ClickMode::Single || double {
+            return KeyPressedProcessor::process(state, String::from("<enter>"));
+        }
 
-            matched_apps.into_iter().map(|(_, app)| app).collect()
-        };
+        Task::none()
+    }
+}
 
-        let application_list = filtered_applications
-            .iter()
-            .enumerate()
-            .map(|(i, application)| {
-                let name = application.name.clone();
-
-                let icon_widget: iced::Element<'_, Message> = match &application.icon {
-                    Icon::Svg(path) => svg(path.clone())
-                        .width(32)
-                        .height(32)
-                        .content_fit(ContentFit::ScaleDown)
-                        .into(),
-                    Icon::Image(path) => image(path.clone())
-                        .width(32)
-                        .height(32)
-                        .content_fit(ContentFit::ScaleDown)
-                        .into(),
-                };
+struct QuickLaunchProcessor;
+impl MessageProcessor<usize> for QuickLaunchProcessor {
+    fn process(state: &mut Astatine, param: usize) -> Task<Message> {
+        if state.preview {
+            return Task::none();
+        }
 
-                button(
-                    row![
-                        icon_widget,
-                        text(name).align_y(iced::alignment::Vertical::Center)
-                    ]
-                    .spacing(10)
-                    .align_y(iced::Alignment::Center)
-                    .padding(Padding::from([2, 0])),
-                )
-                .style(move |_, _| button::Style {
-                    background: if i + 1 == self.focus {
-                        Some(Background::Color(Color::from_rgb8(169, 177, 214)))
-                    } else {
-                        None
-                    },
-                    border: iced::Border {
-                        color: Color::from_rgba8(0, 0, 0, 0.0),
-                        width: 1.0,
-                        radius: iced::border::Radius::new(10),
-                    },
-                    shadow: iced::Shadow {
-                        color: Color::from_rgba8(0, 0, 0, 0.0),
-                        offset: iced::Vector::new(0.0, 0.0),
-                        blur_radius: 0.0,
-                    },
-                    text_color: if i + 1 == self.focus {
-                        Color::from_rgb8(26, 27, 38)
-                    } else {
-                        Color::from_rgb8(169, 177, 214)
-                    },
-                })
-            })
-            .fold(column![], |col, element| col.push(element));
+        if let Some(application) = state.quick_launch().get(param).map(|app| (*app).clone()) {
+            state.launch(&application);
+        }
 
-        container(
-            column![
-                text_input("", &self.search)
-                    .on_input(Message::SearchChanged)
-                    .on_submit(Message::SearchSubmit)
-                    .id("search"),
-                application_list,
-            ]
-            .spacing(16),
-        )
-        .padding(Padding::from([12, 24]))
-        .into()
+        Task::none()
     }
+}
 
-    fn subscription(&self) -> Subscription<Message> {
-        keyboard::on_key_press(|key, _| match key {
-            keyboard::Key::Character(character) => Some(Message::KeyPressed(character.to_string())),
-            keyboard::Key::Named(keyboard::key::Named::Enter) => {
-                Some(Message::KeyPressed(String::from("<enter>")))
-            }
-            _ => None,
-        })
+struct CategorySelectedProcessor;
+impl MessageProcessor<Option<&'static str>> for CategorySelectedProcessor {
+    fn process(state: &mut Astatine, param: Option<&'static str>) -> Task<Message> {
+        state.category = param;
+        state.prev_focus = None;
+        state.focus = 0;
+        state.open_actions = None;
+
+        Task::batch([state.filter_applications(), state.focus_search()])
     }
+}
 
-    fn theme(&self) -> Theme {
-        Theme::TokyoNight
+struct ModifiersChangedProcessor;
+impl MessageProcessor<keyboard::Modifiers> for ModifiersChangedProcessor {
+    fn process(state: &mut Astatine, param: keyboard::Modifiers) -> Task<Message> {
+        state.modifiers = param;
+
+        Task::none()
     }
+}
+
+struct AnimationFrameProcessor;
+impl MessageProcessor<Instant> for AnimationFrameProcessor {
+    fn process(state: &mut Astatine, param: Instant) -> Task<Message> {
+        let done = state.focus_moved.is_none_or(|(_, since)| {
+            param.saturating_duration_since(since) >= state.selection_animation()
+        });
+        if done {
+            state.focus_moved = None;
+        }
 
-    fn run() -> (Self, Task<Message>) {
-        (Astatine::new(), focus_search())
+        Task::none()
     }
 }
 
-fn main() -> iced::Result {
-    iced::application("Astatine", Astatine::update, Astatine::view)
-        .window_size(Size::new(540.0, 620.0))
-        .theme(Astatine::theme)
-        .subscription(Astatine::subscription)
-        .run_with(Astatine::run)
+struct IconResolvedProcessor;
+impl MessageProcessor<(String, Option<Icon>, Option<Color>)> for IconResolvedProcessor {
+    fn process(
+        state: &mut Astatine,
+        (id, icon, accent): (String, Option<Icon>, Option<Color>),
+    ) -> Task<Message> {
+        if let Some(accent) = accent {
+            state.accents.insert(id.clone(), accent);
+        }
+        // A miss stays cached as `Icon::None` so the fallback isn't looked up again
+        state.icons.insert(id, Some(icon.unwrap_or_default()));
+        state.icons_loading = state.icons_loading.saturating_sub(1);
+
+        state.start_icon_lookups()
+    }
 }
 
-fn focus_search() -> Task<Message> {
-    text_input::focus("search")
+#[cfg(feature = "window-count")]
+struct WindowsCountedProcessor;
+#[cfg(feature = "window-count")]
+impl MessageProcessor<WindowCounts> for WindowsCountedProcessor {
+    fn process(state: &mut Astatine, param: WindowCounts) -> Task<Message> {
+        state.window_counts = param;
+
+        Task::none()
+    }
 }
 
-fn execute_app_exec(exec: String) {
-    let mut parts = exec.split_whitespace();
-    if let Some(program) = parts.next() {
-        let args: Vec<&str> = parts.filter(|part| !part.starts_with('%')).collect();
+impl Astatine {
+    fn new(config: Config) -> Self {
+        Self {
+            result_template: Template::parse_or_default(&config.result_format),
+            config,
+            search: String::from(""),
+            applications: Arc::new(Vec::new()),
+            filtered: Vec::new(),
+            conversion: None,
+            search_error: None,
+            filter_task: None,
+            filter_process: None,
+            filter_debounce: None,
+            filter_generation: 0,
+            command_history: CommandHistory::load(),
+            frecency: Arc::new(Frecency::load()),
+            icons: HashMap::new(),
+            accents: HashMap::new(),
+            icon_queue: VecDeque::new(),
+            icons_loading: 0,
+            command_suggestions: Vec::new(),
+            history_cursor: None,
+            active_script: None,
+            script_results: None,
+            focus: 0,
+            prev_focus: None,
+            show_detail: false,
+            open_actions: None,
+            focus_moved: None,
+            confirm_removal: None,
+            last_click: None,
+            modifiers: keyboard::Modifiers::default(),
+            preview: false,
+            substring_match: false,
+            favorites_only: false,
+            categories: Vec::new(),
+            category: None,
+            #[cfg(feature = "window-count")]
+            window_counts: WindowCounts::default(),
+            #[cfg(feature = "window-count")]
+            window_switcher: None,
+            loading: true,
+        }
+    }
+
+    /// Scan for applications off the UI thread, or list the samples in a theme preview
+    fn load_applications(&self) -> Task<Message> {
+        if self.preview {
+            return Task::done(Message::AppsLoaded(sample_applications(&self.config)));
+        }
+
+        let config = self.config.clone();
 
-        if let Err(e) = process::Command::new(program).args(args).spawn() {
-            eprintln!("Failed to execute {}: {}", program, e);
+        #[cfg(feature = "window-count")]
+        if let Some(session) = self.window_switcher {
+            // The apps only lend the windows their names and icons
+            return Task::perform(
+                async move { windows::list(session, &get_applications(&config)) },
+                Message::AppsLoaded,
+            );
         }
-    } else {
-        eprintln!("No command provided.");
+
+        Task::perform(
+            async move { get_applications(&config) },
+            Message::AppsLoaded,
+        )
     }
 
-    process::exit(0);
-}
+    /// How many results fit the window, a row of the grid holding `columns` of them
+    fn visible_results(&self) -> usize {
+        VISIBLE_RESULTS * self.config.columns
+    }
 
-#[derive(Clone)]
-struct Application {
-    name: String,
-    exec: String,
-    icon: Icon,
-}
+    /// The search matched against the candidates, without the active script's trigger
+    fn query(&self) -> &str {
+        match self.active_script {
+            Some(index) => &self.search[self.config.scripts[index].trigger.len()..],
+            None => &self.search,
+        }
+    }
 
-#[derive(Clone)]
-enum Icon {
-    Svg(String),
-    Image(String),
-}
+    /// `match_mode`, unless substring matching was toggled on
+    fn match_mode(&self) -> MatchMode {
+        if self.substring_match {
+            MatchMode::Substring
+        } else {
+            self.config.match_mode
+        }
+    }
+
+    /// The shell command being typed, if the query starts with `>`
+    fn command_query(&self) -> Option<&str> {
+        self.search.strip_prefix('>')
+    }
+
+    /// The embedded icon `search_box.icon` shows for the query's mode
+    fn prompt_glyph(&self) -> &'static str {
+        if self.command_query().is_some() {
+            COMMAND_GLYPH
+        } else if self.active_script.is_some() {
+            SCRIPT_GLYPH
+        } else if paths::is_path_query(&self.search) {
+            PATH_GLYPH
+        } else if self.conversion.is_some() {
+            CONVERSION_GLYPH
+        } else {
+            SEARCH_GLYPH
+        }
+    }
+
+    /// Results above the application list, which shift the applications' focus index
+    fn leading_results(&self) -> usize {
+        usize::from(self.conversion.is_some())
+    }
+
+    /// How long the highlight takes to move from one result to the next
+    fn selection_animation(&self) -> Duration {
+        Duration::from_millis(self.config.selection_animation_ms)
+    }
+
+    /// How highlighted the result at `index` is, from 0 to 1, the focused one fading in and
+    /// the one the focus left fading out
+    fn highlight(&self, index: usize) -> f32 {
+        let Some((previous, since)) = self.focus_moved else {
+            return if index == self.focus { 1.0 } else { 0.0 };
+        };
+        let progress =
+            (since.elapsed().as_secs_f32() / self.selection_animation().as_secs_f32()).min(1.0);
 
-fn get_applications() -> Vec<Application> {
-    let locales = get_languages_from_env();
-    let entries = Iter::new(default_paths())
-        .entries(Some(&locales))
-        .collect::<Vec<_>>();
+        if index == self.focus {
+            progress
+        } else if index == previous {
+            1.0 - progress
+        } else {
+            0.0
+        }
+    }
+
+    /// Border of the focused result, kept visible for users who miss the background alone
+    fn focus_ring(&self) -> iced::Border {
+        iced::Border {
+            color: Color::parse(&self.config.focus_ring.color).unwrap_or(Color::WHITE),
+            width: self.config.focus_ring.width,
+            radius: iced::border::Radius::new(10),
+        }
+    }
 
-    let mut applications = Vec::new();
-    let mut seen_execs = HashSet::new();
+    /// The theme's search box with the configured overrides applied
+    fn search_box_style(&self, theme: &Theme, status: text_input::Status) -> text_input::Style {
+        let config = &self.config.search_box;
+        let color = |value: &Option<String>| value.as_deref().and_then(Color::parse);
+        let default = text_input::default(theme, status);
 
-    let icon_loader = IconLoader::new_gtk().unwrap_or(IconLoader::new());
-    let default_icon = icon_loader
-        .load_icon("application-x-executable")
-        .unwrap()
-        .file_for_size(32)
-        .path()
-        .to_string_lossy()
-        .into_owned();
+        text_input::Style {
+            background: color(&config.background)
+                .map(Background::Color)
+                .unwrap_or(default.background),
+            border: iced::Border {
+                color: color(&config.border_color).unwrap_or(default.border.color),
+                width: config.border_width.unwrap_or(default.border.width),
+                radius: config
+                    .border_radius
+                    .map(iced::border::Radius::new)
+                    .unwrap_or(default.border.radius),
+            },
+            placeholder: color(&config.placeholder_color).unwrap_or(default.placeholder),
+            ..default
+        }
+    }
 
-    for entry in entries {
-        let name = entry.name(&locales).unwrap().into_owned();
-        // Exec is required but some entries ignore that
-        let exec = entry.exec().unwrap_or("").to_string();
-        let icon_name = entry.icon().unwrap_or("").to_string();
+    fn focused_application(&self) -> Option<&Application> {
+        self.focus
+            .checked_sub(1 + self.leading_results())
+            .and_then(|i| self.filtered.get(i))
+    }
 
-        if name.is_empty() || exec.is_empty() || !seen_execs.insert(exec.clone()) {
-            continue;
+    /// Look up icons for the top results that don't have one yet, placeholders show meanwhile
+    fn request_icons(&mut self) -> Task<Message> {
+        // Queued icons of results no longer shown wait until they are shown again
+        let stale: Vec<_> = self.icon_queue.drain(..).collect();
+        for (id, _) in stale {
+            self.icons.remove(&id);
         }
 
-        let icon = if !icon_name.is_empty() {
-            let path = lookup(&icon_name)
-                .with_size(32)
-                .find()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .into_owned();
+        let pending: Vec<(String, String)> = self
+            .filtered
+            .iter()
+            .take(self.visible_results())
+            .chain(self.quick_launch())
+            .filter(|application| !application.icon_name.is_empty())
+            .map(|application| (application.id.clone(), application.icon_name.clone()))
+            .collect();
 
-            if !path.is_empty() {
-                if path.ends_with(".svg") {
-                    Icon::Svg(path)
-                } else {
-                    Icon::Image(path)
-                }
-            } else {
-                Icon::Svg(default_icon.clone())
+        for (id, icon_name) in pending {
+            // Also skips quick launch apps that are among the results
+            if self.icons.contains_key(&id) {
+                continue;
             }
-        } else {
-            Icon::Svg(default_icon.clone())
-        };
+            self.icons.insert(id.clone(), None);
+            self.icon_queue.push_back((id, icon_name));
+        }
+
+        self.start_icon_lookups()
+    }
+
+    /// Start queued icon lookups while fewer than `icon_concurrency` are running
+    fn start_icon_lookups(&mut self) -> Task<Message> {
+        let size = self.config.icon_size;
+        let timeout = Duration::from_millis(self.config.icon_lookup_timeout_ms);
+        let accent_colors = self.config.accent_colors;
+        let mut lookups = Vec::new();
+
+        while self.icons_loading < self.config.icon_concurrency {
+            let Some((id, icon_name)) = self.icon_queue.pop_front() else {
+                break;
+            };
+            self.icons_loading += 1;
+
+            lookups.push(Task::perform(
+                async move {
+                    let icon = resolve_icon_within(&icon_name, size, timeout);
+                    let accent = icon
+                        .as_ref()
+                        .filter(|_| accent_colors)
+                        .and_then(dominant_color);
+                    (icon, accent)
+                },
+                move |(icon, accent)| Message::IconResolved(id.clone(), icon, accent),
+            ));
+        }
+
+        Task::batch(lookups)
+    }
+
+    /// The resolved icon of an application, or its fallback when none was found. `None`
+    /// while it's looked up
+    fn icon_of<'a>(&'a self, application: &'a Application) -> Option<&'a Icon> {
+        match self.icons.get(&application.id) {
+            Some(None) => None,
+            Some(Some(Icon::None)) | None => Some(&application.icon),
+            Some(Some(icon)) => Some(icon),
+        }
+    }
+
+    /// An application's icon at `size`, or the `icon_placeholder` while it's looked up
+    fn icon_widget(&self, application: &Application, size: u16) -> iced::Element<'static, Message> {
+        let icon = match self.icon_of(application) {
+            Some(icon) => icon,
+            None => match self.config.icon_placeholder {
+                IconPlaceholder::Generic => &application.icon,
+                IconPlaceholder::Box => return placeholder_box(size),
+                IconPlaceholder::None => &Icon::None,
+            },
+        };
+
+        match icon {
+            Icon::Svg(path) => svg(path.clone())
+                .width(size)
+                .height(size)
+                .content_fit(ContentFit::ScaleDown)
+                .into(),
+            Icon::Image(path) => image(path.clone())
+                .width(size)
+                .height(size)
+                .content_fit(ContentFit::ScaleDown)
+                .into(),
+            Icon::None => Space::new(size, size).into(),
+        }
+    }
+
+    /// Abort the `filter_command` run waiting for or working on a stale query
+    fn cancel_filter_command(&mut self) {
+        self.filter_debounce = None;
+        self.filter_process = None;
+        self.filter_generation += 1;
+    }
+
+    /// Recompute the visible results for the current search off the UI thread
+    fn filter_applications(&mut self) -> Task<Message> {
+        let applications = match self.active_script {
+            Some(_) => {
+                let Some(lines) = &self.script_results else {
+                    return Task::none();
+                };
+
+                Arc::clone(lines)
+            }
+            None => Arc::clone(&self.applications),
+        };
+        let search = self.query().to_string();
+        let mut config = self.config.clone();
+        config.match_mode = self.match_mode();
+        let frecency = Arc::clone(&self.frecency);
+        let path_query = self.active_script.is_none() && paths::is_path_query(&search);
+        let category = self.category.filter(|_| self.active_script.is_none());
+        let favorites_only = self.favorites_only && self.active_script.is_none();
+
+        self.cancel_filter_command();
+
+        if self.config.filter_command.is_some() && self.active_script.is_none() && !path_query {
+            self.filter_task = None;
+
+            let generation = self.filter_generation;
+            let delay = Duration::from_millis(self.config.filter_debounce_ms);
+
+            let (task, handle) = Task::perform(sleep(delay), move |()| {
+                Message::FilterCommandDue(generation)
+            })
+            .abortable();
+            self.filter_debounce = Some(handle.abort_on_drop());
+
+            return task;
+        }
+
+        let (task, handle) = Task::perform(
+            async move {
+                if path_query {
+                    Ok(paths::list(split_action(&search).0))
+                } else if category.is_some() || favorites_only {
+                    let candidates: Vec<Application> = applications
+                        .iter()
+                        .filter(|application| {
+                            category.is_none_or(|category| {
+                                application.categories.iter().any(|c| c == category)
+                            }) && (!favorites_only || config.is_favorite(&application.id))
+                        })
+                        .cloned()
+                        .collect();
+                    filter_applications(&candidates, &search, &config, &frecency)
+                } else {
+                    filter_applications(&applications, &search, &config, &frecency)
+                }
+            },
+            Message::FilterComputed,
+        )
+        .abortable();
+
+        // Dropping the previous handle aborts a computation for a stale query
+        self.filter_task = Some(handle.abort_on_drop());
+
+        task
+    }
+
+    fn update(&mut self, message: Message) -> iced::Task<Message> {
+        // Waiting out `close_delay_ms`, input now could launch a second app
+        if closing() {
+            return Task::none();
+        }
+
+        let focus = self.focus;
+
+        let task = match message {
+            Message::SearchSubmit => SearchSubmitProcessor::process(self, ()),
+            Message::SearchChanged(param) => SearchChangedProcessor::process(self, param),
+            Message::SearchPasted(param) => SearchPastedProcessor::process(self, param),
+            Message::KeyPressed(param) => {
+                // The search box captures typing while focused, so a plain key with the
+                // focus on it means a click elsewhere took its keyboard focus
+                let unfocused = self.focus == 0 && param.chars().count() == 1;
+                let task = KeyPressedProcessor::process(self, param);
+
+                if unfocused && self.focus == 0 {
+                    Task::batch([task, text_input::focus("search")])
+                } else {
+                    task
+                }
+            }
+            Message::AppsLoaded(param) => AppsLoadedProcessor::process(self, param),
+            Message::FilterComputed(param) => FilterComputedProcessor::process(self, param),
+            Message::ScriptLoaded(index, lines) => {
+                ScriptLoadedProcessor::process(self, (index, lines))
+            }
+            Message::FilterCommandDue(param) => FilterCommandDueProcessor::process(self, param),
+            Message::FilterCommandDone(generation, results) => {
+                FilterCommandDoneProcessor::process(self, (generation, results))
+            }
+            Message::ConfigReloaded(param) => ConfigReloadedProcessor::process(self, param),
+            Message::IconResolved(id, icon, accent) => {
+                IconResolvedProcessor::process(self, (id, icon, accent))
+            }
+            Message::ResultClicked(param) => ResultClickedProcessor::process(self, param),
+            Message::QuickLaunch(param) => QuickLaunchProcessor::process(self, param),
+            Message::CategorySelected(param) => CategorySelectedProcessor::process(self, param),
+            Message::ModifiersChanged(param) => ModifiersChangedProcessor::process(self, param),
+            Message::AnimationFrame(param) => AnimationFrameProcessor::process(self, param),
+            #[cfg(feature = "window-count")]
+            Message::WindowsCounted(param) => WindowsCountedProcessor::process(self, param),
+        };
+
+        // Wherever the focus moved from, the highlight fades over from there
+        if self.focus != focus && !self.selection_animation().is_zero() {
+            self.focus_moved = Some((focus, Instant::now()));
+        }
+
+        task
+    }
+
+    fn view(&self) -> iced::Element<'_, Message> {
+        let detail = self
+            .focused_application()
+            .filter(|_| self.show_detail)
+            .map(|application| self.detail_view(application));
+        let ring = self.focus_ring();
+        let compact = self.config.layout == Layout::Compact;
+
+        let application_list: iced::Element<'_, Message> = if closing() {
+            text("Launching…")
+                .color(Color::from_rgb8(169, 177, 214))
+                .into()
+        } else if let Some(path) = &self.confirm_removal {
+            text(format!(
+                "Delete {}? Press y to confirm, any other key to keep it",
+                path.display()
+            ))
+            .color(Color::from_rgb8(247, 118, 142))
+            .into()
+        } else if let Some(detail) = detail {
+            detail
+        } else if self.loading {
+            text("Loading applications…")
+                .color(Color::from_rgb8(169, 177, 214))
+                .into()
+        } else if self.command_query().is_some() {
+            self.command_suggestions
+                .iter()
+                .enumerate()
+                .map(|(i, command)| {
+                    let focused = i + 1 == self.focus;
+
+                    button(text(command).font(iced::Font::MONOSPACE))
+                        .on_press(Message::ResultClicked(i + 1))
+                        .padding(Padding::from([8, 10]))
+                        .width(iced::Length::Fill)
+                        .style(move |_, _| result_style(focused, ring))
+                })
+                .fold(column![], |col, element| col.push(element))
+                .into()
+        } else if let Some(error) = &self.search_error {
+            text(error).color(Color::from_rgb8(247, 118, 142)).into()
+        } else {
+            let conversion = self.conversion.as_ref().map(|conversion| {
+                let focused = self.focus == 1;
+
+                button(text(conversion).size(18))
+                    .on_press(Message::ResultClicked(1))
+                    .padding(Padding::from([8, 10]))
+                    .width(iced::Length::Fill)
+                    .style(move |_, _| result_style(focused, ring))
+            });
+            let offset = self.leading_results();
+            // Path results are listed by prefix, there is no match to show
+            let highlight_query = (self.active_script.is_some()
+                || !paths::is_path_query(&self.search))
+            .then(|| self.query());
+
+            let columns = self.config.columns;
+            let icon_size = self.config.icon_size;
+            let gap = self.config.icon_text_gap;
+            let row_height = self.config.row_height.filter(|_| columns == 1);
+            // Window padding, then the row's padding and the gap after the icon, or the
+            // cell's padding and the gap between cells
+            let name_width = if columns == 1 {
+                WINDOW_WIDTH - 48.0 - 10.0 - f32::from(gap) - f32::from(icon_size)
+            } else {
+                (WINDOW_WIDTH - 48.0) / columns as f32 - 24.0
+            };
+
+            let results: Vec<iced::Element<'_, Message>> = self
+                .filtered
+                .iter()
+                .enumerate()
+                .map(|(i, application)| {
+                    let focused = i + 1 + offset == self.focus;
+                    let line = self.result_template.render(application);
+                    // Rows past the window aren't seen, so they skip the matching
+                    let positions = highlight_query
+                        .filter(|_| i < self.visible_results())
+                        .map(|query| match_positions(&line, query, self.match_mode()))
+                        .unwrap_or_default();
+                    let badges = self.badges(application);
+                    // Badges take their room from the name
+                    let badges_width: f32 = badges
+                        .iter()
+                        .map(|(label, _)| label.chars().count() as f32 * 6.0 + 16.0)
+                        .sum();
+                    // So does the accent bar, 3 pixels and the gap after it
+                    let accent_width = if columns == 1 && self.accents.contains_key(&application.id)
+                    {
+                        3.0 + f32::from(gap)
+                    } else {
+                        0.0
+                    };
+                    let (name, positions) =
+                        visible_name(&line, &positions, name_width - badges_width - accent_width);
+                    let badges = (!badges.is_empty()).then(|| {
+                        badges
+                            .into_iter()
+                            .fold(row![].spacing(4), |row, (label, color)| {
+                                row.push(badge(label, color))
+                            })
+                    });
+
+                    let icon_widget = self.icon_widget(application, icon_size);
+                    let label = rich_text(highlight_spans(&name, &positions))
+                        .wrapping(text::Wrapping::None);
+
+                    let accent = self.accents.get(&application.id).copied();
+
+                    let content: iced::Element<'_, Message> =
+                        if columns == 1 {
+                            row![]
+                                .push_maybe(accent.map(|color| accent_bar(color, 3, icon_size)))
+                                .push(icon_widget)
+                                .push(label.align_y(iced::alignment::Vertical::Center))
+                                .push_maybe(badges.map(|badges| {
+                                    row![Space::with_width(iced::Length::Fill), badges]
+                                }))
+                                .spacing(gap)
+                                .align_y(iced::Alignment::Center)
+                                // Filling a fixed height row is what centers it vertically
+                                .height(match row_height {
+                                    Some(_) => iced::Length::Fill,
+                                    None => iced::Length::Shrink,
+                                })
+                                .padding(Padding::from([2, 0]))
+                                .into()
+                        } else {
+                            column![icon_widget, label]
+                                .push_maybe(badges)
+                                .push_maybe(accent.map(|color| accent_bar(color, icon_size, 3)))
+                                .spacing(6)
+                                .align_x(iced::Alignment::Center)
+                                .width(iced::Length::Fill)
+                                .into()
+                        };
+
+                    // While its actions are expanded the highlight moves down to them
+                    let highlight = match self.open_actions {
+                        Some(_) if focused => 0.0,
+                        _ => self.highlight(i + 1 + offset),
+                    };
+                    let result = button(content)
+                        .on_press(Message::ResultClicked(i + 1 + offset))
+                        .padding(if columns > 1 {
+                            Padding::from(8)
+                        } else if compact || row_height.is_some() {
+                            // The row height already sets the room above and below
+                            Padding::from([0, if compact { 8 } else { 5 }])
+                        } else {
+                            Padding::from(5)
+                        })
+                        .width(iced::Length::Fill)
+                        .height(row_height.map_or(iced::Length::Shrink, |height| {
+                            iced::Length::Fixed(f32::from(height))
+                        }))
+                        // Whatever the estimate in `visible_name` misses is cut off at the edge
+                        .clip(true)
+                        .style(move |_, _| faded_result_style(highlight, ring));
+
+                    let actions = self.open_actions.filter(|_| focused).map(|selected| {
+                        application
+                            .actions
+                            .iter()
+                            .enumerate()
+                            .map(|(a, action)| {
+                                button(text(&action.name))
+                                    .padding(
+                                        Padding::from([4, 8])
+                                            .left(f32::from(icon_size) + f32::from(gap) + 8.0),
+                                    )
+                                    .width(iced::Length::Fill)
+                                    .style(move |_, _| result_style(a == selected, ring))
+                            })
+                            .fold(column![], |col, element| col.push(element))
+                    });
+
+                    column![result].push_maybe(actions).into()
+                })
+                .collect();
+
+            if columns == 1 {
+                results
+                    .into_iter()
+                    .fold(column![].push_maybe(conversion), |col, element| {
+                        col.push(element)
+                    })
+                    .into()
+            } else {
+                let mut results = results.into_iter();
+                let mut grid = column![].push_maybe(conversion).spacing(8);
+
+                loop {
+                    let cells: Vec<_> = results.by_ref().take(columns).collect();
+                    if cells.is_empty() {
+                        break;
+                    }
+
+                    // Fillers keep the last row's cells as wide as the others
+                    let fillers = columns - cells.len();
+                    grid = grid.push(
+                        (0..fillers)
+                            .fold(iced::widget::Row::with_children(cells), |cells, _| {
+                                cells.push(Space::with_width(iced::Length::Fill))
+                            })
+                            .spacing(8),
+                    );
+                }
+
+                grid.into()
+            }
+        };
+
+        let search = text_input(&self.config.search_box.placeholder, &self.search)
+            .on_input(Message::SearchChanged)
+            .on_paste(Message::SearchPasted)
+            .on_submit(Message::SearchSubmit)
+            .style(|theme, status| self.search_box_style(theme, status))
+            .id("search");
+        let glyph: Option<iced::Element<'_, Message>> = if self.config.search_box.icon {
+            Some(
+                svg(svg::Handle::from_memory(self.prompt_glyph().as_bytes()))
+                    .width(18)
+                    .height(18)
+                    .style(|_, _| svg::Style {
+                        color: Some(Color::from_rgb8(122, 162, 247)),
+                    })
+                    .into(),
+            )
+        } else if compact {
+            Some(
+                text("›")
+                    .size(20)
+                    .color(Color::from_rgb8(122, 162, 247))
+                    .into(),
+            )
+        } else {
+            None
+        };
+        let prompt = row![]
+            .push_maybe(glyph)
+            .push(search)
+            .push_maybe(self.favorites_only.then(|| {
+                text("favorites")
+                    .size(12)
+                    .color(Color::from_rgb8(122, 162, 247))
+            }))
+            .push_maybe(self.substring_match.then(|| {
+                text("substring")
+                    .size(12)
+                    .color(Color::from_rgb8(122, 162, 247))
+            }))
+            .spacing(8)
+            .align_y(iced::Alignment::Center);
+
+        let chips = (self.config.category_chips && !self.categories.is_empty()).then(|| {
+            self.categories
+                .iter()
+                .map(|&category| {
+                    let selected = self.category == Some(category);
+                    let label = MAIN_CATEGORIES
+                        .iter()
+                        .find(|(name, _)| *name == category)
+                        .map_or(category, |(_, label)| *label);
+
+                    button(text(label).size(12))
+                        .on_press(Message::CategorySelected((!selected).then_some(category)))
+                        .padding(Padding::from([2, 8]))
+                        .style(move |_, _| chip_style(selected))
+                })
+                .fold(row![].spacing(6), |chips, chip| chips.push(chip))
+                .wrap()
+        });
+
+        let quick_launch = (self.config.quick_launch > 0 && !self.loading).then(|| {
+            let icon_size = self.config.icon_size;
+
+            self.quick_launch()
+                .into_iter()
+                .enumerate()
+                .map(|(i, application)| {
+                    let icon = self.icon_widget(application, icon_size);
+
+                    button(
+                        column![
+                            icon,
+                            text(i + 1).size(10).color(Color::from_rgb8(86, 95, 137))
+                        ]
+                        .spacing(2)
+                        .align_x(iced::Alignment::Center),
+                    )
+                    .on_press(Message::QuickLaunch(i))
+                    .padding(4)
+                    .style(move |_, status| result_style(status == button::Status::Hovered, ring))
+                })
+                .fold(row![].spacing(8), |bar, item| bar.push(item))
+        });
+
+        let hints = self.config.show_hints.then(|| {
+            text(
+                KEY_HINTS
+                    .iter()
+                    .map(|(key, description)| format!("{} {}", key, description))
+                    .collect::<Vec<_>>()
+                    .join(" · "),
+            )
+            .size(12)
+            .color(Color::from_rgb8(86, 95, 137))
+        });
+        // With a footer the results take the remaining height, so it stays at the bottom
+        let application_list: iced::Element<'_, Message> = if hints.is_some() {
+            container(application_list)
+                .height(iced::Length::Fill)
+                .clip(true)
+                .into()
+        } else {
+            application_list
+        };
+
+        container(
+            column![]
+                .push_maybe(quick_launch)
+                .push(prompt)
+                .push_maybe(chips)
+                .push(application_list)
+                .push_maybe(hints)
+                .spacing(if compact { 4 } else { 16 }),
+        )
+        .padding(Padding::from([12, 24]))
+        .into()
+    }
+
+    /// Launch an application like Enter does, raising its open window instead if configured
+    fn launch(&mut self, application: &Application) {
+        if application.source == Source::Window {
+            return self.switch_to(application);
+        }
+
+        // Lines of the `filter_command` come and go, they aren't worth remembering
+        if application.source != Source::Script {
+            self.record_launch(&application.id);
+        }
+
+        if self.raise_existing(application) {
+            return launch::close(&self.config);
+        }
+
+        execute_app_exec(application, &self.config, false);
+    }
+
+    /// Activate the window a switcher result stands for
+    #[cfg(feature = "window-count")]
+    fn switch_to(&self, window: &Application) {
+        let Some(session) = self.window_switcher else {
+            return;
+        };
+
+        if self.config.safe_mode {
+            logger::info!("Safe mode, not switching to {}", window.name);
+            return;
+        }
+
+        windows::activate(session, window);
+        launch::close(&self.config);
+    }
+
+    #[cfg(not(feature = "window-count"))]
+    fn switch_to(&self, _: &Application) {}
+
+    /// Count a launch towards frecency, unless in safe mode where nothing launches
+    fn record_launch(&mut self, id: &str) {
+        if !self.config.safe_mode {
+            Arc::make_mut(&mut self.frecency).record(id);
+        }
+    }
+
+    /// Focus the search box, selecting its text when `select_on_focus` is set
+    fn focus_search(&self) -> Task<Message> {
+        let focus = text_input::focus("search");
+
+        if self.config.select_on_focus && !self.search.is_empty() {
+            focus.chain(text_input::select_all("search"))
+        } else {
+            focus
+        }
+    }
+
+    /// The most used applications, for the quick launch bar
+    fn quick_launch(&self) -> Vec<&Application> {
+        self.frecency
+            .top(&self.applications, self.config.quick_launch.min(9))
+    }
+
+    /// Raise the app's open window instead of launching another instance, when
+    /// configured and Alt isn't held to force one
+    fn raise_existing(&self, application: &Application) -> bool {
+        self.config.raise_existing
+            && !self.config.safe_mode
+            && !self.modifiers.alt()
+            && self.raise_window(application)
+    }
+
+    #[cfg(feature = "window-count")]
+    fn raise_window(&self, application: &Application) -> bool {
+        self.window_counts.raise(application)
+    }
+
+    #[cfg(not(feature = "window-count"))]
+    fn raise_window(&self, _: &Application) -> bool {
+        false
+    }
+
+    /// Labels beside a result: how the app was installed and how many windows it has open
+    fn badges(&self, application: &Application) -> Vec<(String, Color)> {
+        let packaging = Some(application.packaging)
+            .filter(|packaging| self.config.package_badges && *packaging != Packaging::Native)
+            .map(|packaging| (packaging.label().to_string(), Color::from_rgb8(86, 95, 137)));
+        let windows = Some(self.window_count(application))
+            .filter(|count| *count > 0)
+            .map(|count| (count.to_string(), Color::from_rgb8(122, 162, 247)));
+
+        let usage = self
+            .frecency
+            .usage(&application.id)
+            .filter(|_| self.config.show_usage)
+            .map(|(count, age)| {
+                let color = match recency(age) {
+                    r if r >= 4.0 => Color::from_rgb8(158, 206, 106),
+                    r if r >= 2.0 => Color::from_rgb8(122, 162, 247),
+                    _ => Color::from_rgb8(86, 95, 137),
+                };
+                (format!("×{}", count), color)
+            });
+
+        packaging.into_iter().chain(windows).chain(usage).collect()
+    }
+
+    #[cfg(feature = "window-count")]
+    fn window_count(&self, application: &Application) -> usize {
+        // Every switcher result is a single window already
+        if self.window_switcher.is_some() {
+            return 0;
+        }
+
+        self.window_counts.of(application)
+    }
+
+    #[cfg(not(feature = "window-count"))]
+    fn window_count(&self, _: &Application) -> usize {
+        0
+    }
+
+    fn detail_view<'a>(&self, application: &'a Application) -> iced::Element<'a, Message> {
+        let size = self.config.icon_size * 2;
+        let icon = self.icon_widget(application, size);
+
+        let field = |label: &'a str, value: String| {
+            column![
+                text(label).size(12).color(Color::from_rgb8(122, 162, 247)),
+                text(value).color(Color::from_rgb8(169, 177, 214)),
+            ]
+            .spacing(2)
+        };
+
+        let optional = |value: &Option<String>| value.clone().unwrap_or_else(|| String::from("—"));
+
+        column![
+            row![icon, text(&application.name).size(24)]
+                .spacing(16)
+                .align_y(iced::Alignment::Center),
+            field("Generic name", optional(&application.generic_name)),
+            field("Comment", optional(&application.comment)),
+            field("Categories", application.categories.join(", ")),
+            field("Exec", application.exec.clone()),
+        ]
+        .push_maybe(application.url.clone().map(|url| field("URL", url)))
+        .push(field(
+            "Source",
+            match (&application.source_path, application.source) {
+                (Some(path), _) => path.display().to_string(),
+                (None, Source::Custom) => String::from("Config entry"),
+                (None, _) => String::from("—"),
+            },
+        ))
+        .spacing(12)
+        .into()
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        // Redraws only while the highlight fades, the window is idle otherwise
+        let frames = match self.focus_moved {
+            Some(_) => window::frames().map(Message::AnimationFrame),
+            None => Subscription::none(),
+        };
+
+        let events = iced::event::listen_with(|event, status, _| match event {
+            iced::Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. }) => {
+                // Ctrl shortcuts and Escape work while typing, everything else only outside
+                // the search box. Enter there arrives as `SearchSubmit` instead
+                let escape = key == keyboard::Key::Named(keyboard::key::Named::Escape);
+                let enter = key == keyboard::Key::Named(keyboard::key::Named::Enter);
+                if status == iced::event::Status::Captured
+                    && (!modifiers.control() || enter)
+                    && !escape
+                {
+                    return None;
+                }
+
+                key_name(key, modifiers).map(Message::KeyPressed)
+            }
+            iced::Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                Some(Message::ModifiersChanged(modifiers))
+            }
+            _ => None,
+        });
+
+        Subscription::batch([events, frames])
+    }
+
+    fn theme(&self) -> Theme {
+        Theme::TokyoNight
+    }
+
+    /// The theme's background at `window_opacity`
+    fn style(&self, theme: &Theme) -> application::Appearance {
+        let palette = theme.palette();
+
+        application::Appearance {
+            background_color: palette
+                .background
+                .scale_alpha(self.config.window_opacity.clamp(0.0, 1.0)),
+            text_color: palette.text,
+        }
+    }
+
+    fn run(config: Config, args: Args, session: Session) -> (Self, Task<Message>) {
+        BOOTED.store(true, Ordering::Relaxed);
+        let prefix = args.mode.and_then(|mode| mode_prefix(&mode, &config));
+        let mut state = Astatine::new(config);
+        state.preview = args.theme_preview;
+        if state.preview {
+            logger::info!("Previewing the theme, edit the config and press Ctrl+R to see changes");
+        }
+
+        #[cfg(feature = "window-count")]
+        {
+            state.window_switcher = args.windows.then_some(session);
+        }
+
+        let focus = state.focus_search();
+        let blur = request_blur(&state.config, session);
+        let load_applications = state.load_applications();
+        #[cfg(feature = "window-count")]
+        let count_windows = Task::perform(
+            async move { WindowCounts::query(session) },
+            Message::WindowsCounted,
+        );
+        #[cfg(not(feature = "window-count"))]
+        let count_windows = {
+            let _ = session;
+            Task::none()
+        };
+        let start_mode = match prefix {
+            Some(prefix) => Task::batch([
+                SearchChangedProcessor::process(&mut state, prefix),
+                text_input::move_cursor_to_end("search"),
+            ]),
+            None => Task::none(),
+        };
+
+        (
+            state,
+            Task::batch([focus, blur, load_applications, count_windows, start_mode]),
+        )
+    }
+}
+
+fn main() -> iced::Result {
+    let args = Args::parse();
+    // Applied before loading too, so `--quiet` also silences config warnings
+    if let Some(level) = args.log_level {
+        logger::set_level(level);
+    }
+    for arg in &args.unknown {
+        logger::warn!("Unknown argument: {}", arg);
+    }
+
+    let mut config = Config::load();
+    config.apply_args(&args);
+    logger::set_level(config.log_level);
+
+    if args.dump_json {
+        dump_json(&config);
+        return Ok(());
+    }
+
+    if args.print_paths {
+        print_paths(&config);
+        return Ok(());
+    }
+
+    if args.reset_history {
+        Frecency::reset();
+        return Ok(());
+    }
+
+    if let Some(path) = &args.validate {
+        print_validation(path, &config);
+    }
+
+    if let Some(id) = &args.launch {
+        launch_desktop_id(id, &config);
+    }
+
+    if args.windows && !cfg!(feature = "window-count") {
+        logger::error!("--windows needs a build with the window-count feature");
+        process::exit(1);
+    }
+
+    if !Session::display_available() {
+        logger::error!(
+            "No display to open a window on, WAYLAND_DISPLAY, WAYLAND_SOCKET and DISPLAY are \
+             all unset. Run from a graphical session"
+        );
+        process::exit(1);
+    }
+
+    let session = Session::detect(config.session);
+
+    // Winit panics rather than erroring when it can't connect to the display server
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        iced::application("Astatine", Astatine::update, Astatine::view)
+            .window(session.window_settings(config.window_opacity < 1.0))
+            .window_size(Size::new(WINDOW_WIDTH, 620.0))
+            .theme(Astatine::theme)
+            .style(Astatine::style)
+            .subscription(Astatine::subscription)
+            .run_with(move || Astatine::run(config, args, session))
+    }));
+
+    match result {
+        Ok(Err(e)) => {
+            report_startup_error(&e);
+            process::exit(1);
+        }
+        Ok(result) => result,
+        Err(_) if !BOOTED.load(Ordering::Relaxed) => {
+            logger::error!(
+                "Failed to connect to the display server ({})",
+                display_variables()
+            );
+            process::exit(1);
+        }
+        Err(payload) => panic::resume_unwind(payload),
+    }
+}
+
+/// Ask the compositor to blur behind the window once it's open, where it can be asked
+#[cfg(feature = "blur")]
+fn request_blur(config: &Config, session: Session) -> Task<Message> {
+    if !config.blur || config.window_opacity >= 1.0 {
+        return Task::none();
+    }
+
+    if session == Session::Wayland {
+        logger::debug!("Blur on Wayland is up to the compositor's rules");
+        return Task::none();
+    }
+
+    // On X11 the raw ID is the X window ID
+    window::get_oldest()
+        .and_then(window::get_raw_id::<Message>)
+        .then(|raw_id| {
+            Task::future(async move {
+                if let Err(e) = blur::request(raw_id as u32) {
+                    logger::warn!("Failed to request blur: {}", e);
+                }
+            })
+        })
+        .discard()
+}
+
+#[cfg(not(feature = "blur"))]
+fn request_blur(config: &Config, _: Session) -> Task<Message> {
+    if config.blur && config.window_opacity < 1.0 {
+        logger::info!("blur needs a build with the blur feature, the window is only translucent");
+    }
+
+    Task::none()
+}
+
+/// Wait `delay` on a thread of its own, not an executor worker, so aborting it frees
+/// everything but that thread right away
+async fn sleep(delay: Duration) {
+    let (sender, receiver) = oneshot::channel();
+    thread::spawn(move || {
+        thread::sleep(delay);
+        let _ = sender.send(());
+    });
+
+    let _ = receiver.await;
+}
+
+/// Set once iced boots the app, after which panics are the app's own
+static BOOTED: AtomicBool = AtomicBool::new(false);
+
+fn display_variables() -> String {
+    ["WAYLAND_DISPLAY", "DISPLAY"]
+        .map(|var| {
+            let value = std::env::var(var).unwrap_or_else(|_| String::from("unset"));
+            format!("{}={}", var, value)
+        })
+        .join(", ")
+}
+
+/// Explain why the window couldn't open and what to try, iced's own errors being terse
+fn report_startup_error(error: &iced::Error) {
+    match error {
+        iced::Error::WindowCreationFailed(e) => {
+            logger::error!("Failed to open a window: {}", e);
+            logger::error!(
+                "The display server refused it ({}), check it is still running. On Wayland, \
+                 running with WAYLAND_DISPLAY unset opens the window through XWayland instead",
+                display_variables()
+            );
+        }
+        iced::Error::GraphicsCreationFailed(e) => {
+            // Without ICED_BACKEND iced already tried the software renderer after the GPU one
+            logger::error!("Failed to set up rendering: {:?}", e);
+            logger::error!(
+                "ICED_BACKEND={}. Try ICED_BACKEND=tiny-skia to force the software renderer, \
+                 or WGPU_BACKEND=gl to render through OpenGL instead of Vulkan",
+                std::env::var("ICED_BACKEND").unwrap_or_else(|_| String::from("unset"))
+            );
+        }
+        iced::Error::ExecutorCreationFailed(e) => {
+            logger::error!("Failed to start the async executor: {}", e);
+        }
+    }
+}
+
+/// The freedesktop main categories offered as chips, with their labels
+const MAIN_CATEGORIES: &[(&str, &str)] = &[
+    ("AudioVideo", "Media"),
+    ("Development", "Development"),
+    ("Education", "Education"),
+    ("Game", "Games"),
+    ("Graphics", "Graphics"),
+    ("Network", "Internet"),
+    ("Office", "Office"),
+    ("Science", "Science"),
+    ("Settings", "Settings"),
+    ("System", "System"),
+    ("Utility", "Utilities"),
+];
+
+/// Rows that fit the window, the only ones whose icons are looked up right away
+const VISIBLE_RESULTS: usize = 16;
+
+const WINDOW_WIDTH: f32 = 540.0;
+
+/// Longest gap between two clicks on a result that still counts as a double click
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+
+/// Rough width of a glyph at the default text size, for guessing how much of a name fits
+const AVERAGE_CHAR_WIDTH: f32 = 8.0;
+
+/// The part of a result line that fits in `width` pixels, with ellipses where it was cut
+/// and the matched positions moved along with it
+///
+/// Lines are cut at the end unless that would hide the match, in which case the window
+/// slides right so the match stays in view.
+fn visible_name(line: &str, positions: &[usize], width: f32) -> (String, Vec<usize>) {
+    let max_chars = (width / AVERAGE_CHAR_WIDTH) as usize;
+    let chars: Vec<char> = line.chars().collect();
+
+    if chars.len() <= max_chars {
+        return (line.to_string(), positions.to_vec());
+    }
+
+    let match_end = positions.last().map_or(0, |last| last + 1);
+    let start = if match_end < max_chars {
+        0
+    } else {
+        // Room for an ellipsis on both sides, the match's start wins if it's too long
+        let first = positions.first().copied().unwrap_or_default();
+        first.min(match_end.saturating_sub(max_chars.saturating_sub(2)))
+    };
+    let leading = usize::from(start > 0);
+    let end = (start + max_chars.saturating_sub(1 + leading)).min(chars.len());
+
+    let mut visible = String::new();
+    if start > 0 {
+        visible.push('…');
+    }
+    visible.extend(&chars[start..end]);
+    if end < chars.len() {
+        visible.push('…');
+    }
+
+    let positions = positions
+        .iter()
+        .filter(|&&p| (start..end).contains(&p))
+        .map(|p| p - start + leading)
+        .collect();
+
+    (visible, positions)
+}
+
+/// Spans of `name` with the matched characters picked out in the accent color
+fn highlight_spans<'a>(name: &str, positions: &[usize]) -> Vec<text::Span<'a, Message>> {
+    let mut spans = Vec::new();
+    let mut run = String::new();
+    let mut run_matched = false;
+
+    for (i, c) in name.chars().enumerate() {
+        let matched = positions.contains(&i);
+        if matched != run_matched && !run.is_empty() {
+            spans.push(highlight_span(std::mem::take(&mut run), run_matched));
+        }
+        run_matched = matched;
+        run.push(c);
+    }
+    if !run.is_empty() {
+        spans.push(highlight_span(run, run_matched));
+    }
+
+    spans
+}
+
+fn highlight_span<'a>(run: String, matched: bool) -> text::Span<'a, Message> {
+    let run = span(run);
+    if matched {
+        run.color(Color::from_rgb8(122, 162, 247)).underline(true)
+    } else {
+        run
+    }
+}
+
+fn result_style(focused: bool, ring: iced::Border) -> button::Style {
+    faded_result_style(if focused { 1.0 } else { 0.0 }, ring)
+}
+
+/// A result's look partway between plain at 0 and focused at 1
+fn faded_result_style(highlight: f32, ring: iced::Border) -> button::Style {
+    let background = Color::from_rgb8(169, 177, 214);
+
+    button::Style {
+        background: (highlight > 0.0).then_some(Background::Color(Color {
+            a: highlight,
+            ..background
+        })),
+        border: iced::Border {
+            color: Color {
+                a: ring.color.a * highlight,
+                ..ring.color
+            },
+            ..ring
+        },
+        shadow: iced::Shadow {
+            color: Color::from_rgba8(0, 0, 0, 0.0),
+            offset: iced::Vector::new(0.0, 0.0),
+            blur_radius: 0.0,
+        },
+        text_color: mix(background, Color::from_rgb8(26, 27, 38), highlight),
+    }
+}
+
+/// The color `t` of the way from `from` to `to`
+fn mix(from: Color, to: Color, t: f32) -> Color {
+    Color {
+        r: from.r + (to.r - from.r) * t,
+        g: from.g + (to.g - from.g) * t,
+        b: from.b + (to.b - from.b) * t,
+        a: from.a + (to.a - from.a) * t,
+    }
+}
+
+/// The query prefix `--mode` starts with: a built-in mode or a script's trigger, with or
+/// without its trailing colon
+fn mode_prefix(mode: &str, config: &Config) -> Option<String> {
+    let prefix = match mode {
+        "command" => String::from(">"),
+        "file" => String::from("~/"),
+        "regex" => String::from("re:"),
+        _ => {
+            let script = config.scripts.iter().find(|script| {
+                script.trigger == mode || script.trigger.strip_suffix(':') == Some(mode)
+            });
+
+            match script {
+                Some(script) => script.trigger.clone(),
+                None => {
+                    logger::warn!(
+                        "Unknown mode {}, expected command, file, regex or a script trigger",
+                        mode
+                    );
+                    return None;
+                }
+            }
+        }
+    };
+
+    Some(prefix)
+}
+
+/// The bindings listed by `show_hints`, kept next to `KeyPressedProcessor`'s match
+const KEY_HINTS: &[(&str, &str)] = &[
+    ("j/k", "navigate"),
+    ("↵", "launch"),
+    ("⇧↵", "keep terminal open"),
+    ("→", "actions"),
+    ("space", "details"),
+    ("^S", "substring"),
+    ("^F", "favorites"),
+    ("q", "quit"),
+];
+
+// Glyphs for `search_box.icon`, drawn in one color so only their shapes matter
+const SEARCH_GLYPH: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="none" stroke="black" stroke-width="2" stroke-linecap="round"><circle cx="10" cy="10" r="6"/><path d="M14.5 14.5 20 20"/></svg>"#;
+const COMMAND_GLYPH: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="none" stroke="black" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><path d="M5 7l5 5-5 5M12 18h7"/></svg>"#;
+const SCRIPT_GLYPH: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="none" stroke="black" stroke-width="2" stroke-linecap="round"><path d="M9 6h11M9 12h11M9 18h11M4 6h.01M4 12h.01M4 18h.01"/></svg>"#;
+const PATH_GLYPH: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="none" stroke="black" stroke-width="2" stroke-linejoin="round"><path d="M3 7a2 2 0 0 1 2-2h4l2 2h8a2 2 0 0 1 2 2v8a2 2 0 0 1-2 2H5a2 2 0 0 1-2-2z"/></svg>"#;
+const CONVERSION_GLYPH: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="none" stroke="black" stroke-width="2" stroke-linecap="round"><rect x="5" y="3" width="14" height="18" rx="2"/><path d="M8 7h8M8 12h.01M12 12h.01M16 12h.01M8 16h.01M12 16h.01M16 16h.01"/></svg>"#;
+
+/// A neutral rounded square standing in for an icon being looked up
+fn placeholder_box(size: u16) -> iced::Element<'static, Message> {
+    container(Space::new(size, size))
+        .style(|_| container::Style {
+            background: Some(Background::Color(Color::from_rgba8(86, 95, 137, 0.35))),
+            border: iced::Border::default().rounded(6),
+            ..Default::default()
+        })
+        .into()
+}
+
+/// A strip in a result's accent color
+fn accent_bar<'a>(color: Color, width: u16, height: u16) -> iced::Element<'a, Message> {
+    container(Space::new(width, height))
+        .style(move |_| container::Style {
+            background: Some(Background::Color(color)),
+            border: iced::Border::default().rounded(2),
+            ..Default::default()
+        })
+        .into()
+}
+
+fn chip_style(selected: bool) -> button::Style {
+    let accent = Color::from_rgb8(122, 162, 247);
+
+    button::Style {
+        background: selected.then_some(Background::Color(accent)),
+        text_color: if selected {
+            Color::from_rgb8(26, 27, 38)
+        } else {
+            Color::from_rgb8(169, 177, 214)
+        },
+        border: iced::Border {
+            color: if selected {
+                accent
+            } else {
+                Color::from_rgb8(86, 95, 137)
+            },
+            width: 1.0,
+            radius: 10.0.into(),
+        },
+        ..Default::default()
+    }
+}
+
+/// A small rounded label on a result
+fn badge<'a>(label: String, color: Color) -> iced::Element<'a, Message> {
+    container(text(label).size(11))
+        .padding(Padding::from([1, 6]))
+        .style(move |_| container::Style {
+            background: Some(Background::Color(color)),
+            text_color: Some(Color::from_rgb8(26, 27, 38)),
+            border: iced::Border::default().rounded(8),
+            ..Default::default()
+        })
+        .into()
+}
+
+/// The quick launch slot of a number key, `1` or `<C-1>` being the first
+fn quick_launch_slot(key: &str) -> Option<usize> {
+    let digit = key
+        .strip_prefix("<C-")
+        .and_then(|key| key.strip_suffix('>'))
+        .unwrap_or(key);
+
+    match digit.parse::<usize>() {
+        Ok(n @ 1..=9) => Some(n - 1),
+        _ => None,
+    }
+}
+
+/// Vim style notation for a key press, e.g. `j`, `<enter>` or `<C-i>`
+fn key_name(key: keyboard::Key, modifiers: keyboard::Modifiers) -> Option<String> {
+    use keyboard::key::Named;
+
+    let name = match key {
+        keyboard::Key::Character(character) if modifiers.control() => {
+            format!("<C-{}>", character)
+        }
+        keyboard::Key::Character(character) => character.to_string(),
+        keyboard::Key::Named(Named::Enter) if modifiers.shift() => String::from("<S-enter>"),
+        keyboard::Key::Named(Named::Enter) if modifiers.control() => String::from("<C-enter>"),
+        keyboard::Key::Named(Named::Enter) => String::from("<enter>"),
+        keyboard::Key::Named(Named::ArrowUp) => String::from("<up>"),
+        keyboard::Key::Named(Named::ArrowDown) => String::from("<down>"),
+        keyboard::Key::Named(Named::Tab) => String::from("<tab>"),
+        keyboard::Key::Named(Named::ArrowLeft) if modifiers.control() => String::from("<C-left>"),
+        keyboard::Key::Named(Named::ArrowRight) if modifiers.control() => String::from("<C-right>"),
+        keyboard::Key::Named(Named::ArrowLeft) => String::from("<left>"),
+        keyboard::Key::Named(Named::ArrowRight) => String::from("<right>"),
+        keyboard::Key::Named(Named::Space) => String::from("<space>"),
+        keyboard::Key::Named(Named::Escape) => String::from("<esc>"),
+        keyboard::Key::Named(Named::Delete) => String::from("<delete>"),
+        _ => return None,
+    };
+
+    Some(name)
+}
+
+/// Entries covering what a result can show: icons, generic names, comments and actions
+const SAMPLE_ENTRIES: &[&str] = &[
+    "Name=Firefox\nGenericName=Web Browser\nComment=Browse the web\nExec=firefox %u\n\
+     Icon=firefox\nActions=private;\n\n[Desktop Action private]\nName=New Private Window\n\
+     Exec=firefox --private-window",
+    "Name=Files\nGenericName=File Manager\nExec=nautilus\nIcon=system-file-manager",
+    "Name=Terminal\nComment=Use the command line\nExec=foot\nIcon=utilities-terminal",
+    "Name=Text Editor\nGenericName=Editor\nExec=gedit %U\nIcon=accessories-text-editor",
+    "Name=Image Viewer With An Unusually Long Name For Testing Truncation\nExec=eog %U\n\
+     Icon=image-viewer",
+    "Name=Settings\nExec=settings\nIcon=preferences-system",
+];
+
+/// Sample results for `--theme-preview`, built like real entries so they look the same
+fn sample_applications(config: &Config) -> Vec<Application> {
+    let locales: &[String] = &[];
+    let entries = SAMPLE_ENTRIES
+        .iter()
+        .enumerate()
+        .filter_map(|(i, body)| {
+            let input = format!("[Desktop Entry]\nType=Application\n{}\n", body);
+            DesktopEntry::from_str(
+                format!("/preview/sample{}.desktop", i),
+                &input,
+                Some(locales),
+            )
+            .ok()
+        })
+        .collect();
+
+    build_applications(entries, locales, config, &Icon::None)
+}
+
+#[cfg(feature = "json")]
+fn dump_json(config: &Config) {
+    let mut applications = get_applications(config);
+    for application in &mut applications {
+        if let Some(icon) = resolve_icon(&application.icon_name, config.icon_size) {
+            application.icon = icon;
+        }
+    }
+
+    match serde_json::to_string_pretty(&applications) {
+        Ok(json) => println!("{}", json),
+        Err(e) => logger::error!("Failed to serialize applications: {}", e),
+    }
+}
+
+#[cfg(not(feature = "json"))]
+fn dump_json(_: &Config) {
+    logger::error!("--dump-json requires building with the `json` feature");
+    process::exit(1);
+}
+
+fn print_paths(config: &Config) {
+    for var in ["XDG_DATA_HOME", "XDG_DATA_DIRS"] {
+        match std::env::var(var) {
+            Ok(value) => println!("{}={}", var, value),
+            Err(_) => println!("{} is unset, using the default", var),
+        }
+    }
+    println!();
+
+    let configured = default_paths().map(|path| (path, "xdg")).chain(
+        config
+            .extra_app_dirs
+            .iter()
+            .map(|path| (path.clone(), "config")),
+    );
+
+    for (i, (path, source)) in configured.enumerate() {
+        let missing = if path.is_dir() { "" } else { " (missing)" };
+        println!("{:>2}. [{}] {}{}", i + 1, source, path.display(), missing);
+    }
+}
+
+/// Launch the entry with desktop ID `id` the way Enter would, exiting with 1 when no
+/// shown entry has it
+fn launch_desktop_id(id: &str, config: &Config) {
+    let id = id.strip_suffix(".desktop").unwrap_or(id);
+    let Some(application) = get_applications(config)
+        .into_iter()
+        .find(|application| application.id == id)
+    else {
+        logger::error!("No application with desktop ID {}", id);
+        process::exit(1);
+    };
+
+    if !config.safe_mode {
+        Frecency::load().record(&application.id);
+    }
+    execute_app_exec(&application, config, false);
+
+    // Without a window there's no focus to hand over, so no reason to wait
+    process::exit(0);
+}
+
+/// Report whether a `.desktop` file would be shown and why not, exiting with 1 unless it passes
+fn print_validation(path: &Path, config: &Config) {
+    let report = match validate::validate(path, config) {
+        Ok(report) => report,
+        Err(e) => {
+            logger::error!("{}", e);
+            process::exit(1);
+        }
+    };
 
-        applications.push(Application { name, exec, icon });
+    let verdict = if report.passed() { "PASS" } else { "FAIL" };
+    let shown = if report.shown { "shown" } else { "not shown" };
+    println!("{} {} ({})", verdict, path.display(), shown);
+    for problem in &report.problems {
+        println!("  - {}", problem);
     }
 
-    applications
+    process::exit(if report.passed() { 0 } else { 1 });
 }
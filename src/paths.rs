@@ -0,0 +1,76 @@
+use crate::apps::{Application, Icon, Packaging, Source};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Entries listed for a path query, enough to complete any real directory
+const MAX_ENTRIES: usize = 200;
+
+/// Queries starting with `/` or `~/` browse the filesystem instead of applications
+pub fn is_path_query(query: &str) -> bool {
+    query.starts_with('/') || query == "~" || query.starts_with("~/")
+}
+
+/// Entries of the query's directory whose names start with its last component,
+/// directories first. Choosing one opens it with `xdg-open`
+pub fn list(query: &str) -> Vec<Application> {
+    let expanded = match (query.strip_prefix('~'), dirs::home_dir()) {
+        (Some(rest), Some(home)) => format!("{}{}", home.display(), rest),
+        _ => query.to_string(),
+    };
+    let (dir, prefix) = match expanded.rfind('/') {
+        Some(i) => (&expanded[..=i], &expanded[i + 1..]),
+        None => (expanded.as_str(), ""),
+    };
+
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut entries: Vec<(bool, String)> = read_dir
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            // Dotfiles only show up once the query asks for them
+            let wanted =
+                name.starts_with(prefix) && (prefix.starts_with('.') || !name.starts_with('.'));
+
+            wanted.then(|| (entry.path().is_dir(), name))
+        })
+        .collect();
+    entries.sort_by(|(a_dir, a), (b_dir, b)| b_dir.cmp(a_dir).then_with(|| a.cmp(b)));
+
+    entries
+        .into_iter()
+        .take(MAX_ENTRIES)
+        .map(|(is_dir, name)| {
+            let path = PathBuf::from(dir).join(&name);
+            let display = format!("{}{}", path.display(), if is_dir { "/" } else { "" });
+
+            Application {
+                id: format!("path:{}", path.display()),
+                source: Source::Path,
+                packaging: Packaging::Native,
+                name: display,
+                transliterated_name: None,
+                url: Some(path.to_string_lossy().into_owned()),
+                actions: Vec::new(),
+                from_action: false,
+                generic_name: None,
+                comment: None,
+                keywords: Vec::new(),
+                haystack: String::new(),
+                exec: String::new(),
+                icon: Icon::None,
+                icon_name: String::new(),
+                categories: Vec::new(),
+                terminal: false,
+                working_dir: None,
+                extras: HashMap::new(),
+                source_path: None,
+                wm_class: None,
+                dbus_activatable: false,
+            }
+        })
+        .collect()
+}
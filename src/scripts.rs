@@ -0,0 +1,174 @@
+use crate::apps::{Application, Icon, Packaging, Source};
+use crate::config::{Config, ScriptConfig};
+use crate::{launch, logger};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::os::unix::process::CommandExt;
+use std::process::{self, ChildStdout, Stdio};
+use std::thread;
+
+/// Run a script and turn each line of its output into a result
+///
+/// Lines are either a plain `name`, which is passed back to the script when chosen,
+/// or `name<TAB>command`, where the command runs through the shell instead.
+pub fn run_script(script: &ScriptConfig) -> Vec<Application> {
+    let output = match process::Command::new("sh")
+        .arg("-c")
+        .arg(&script.command)
+        .output()
+    {
+        Ok(output) => output,
+        Err(e) => {
+            logger::warn!("Failed to run script {}: {}", script.command, e);
+            return Vec::new();
+        }
+    };
+
+    if !output.status.success() {
+        logger::warn!("Script {} exited with {}", script.command, output.status);
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(i, line)| {
+            let (name, action) = line.split_once('\t').unwrap_or((line, ""));
+
+            line_result(format!("script:{}:{}", script.trigger, i), name, action)
+        })
+        .collect()
+}
+
+/// A `filter_command` run, killed together with everything it started when dropped
+pub struct FilterProcess(Option<process::Child>);
+
+impl Drop for FilterProcess {
+    fn drop(&mut self) {
+        let Some(mut child) = self.0.take() else {
+            return;
+        };
+
+        // Background jobs of the shell would otherwise keep stdout open, and so the
+        // thread reading it busy. The shell isn't reaped yet, so its group ID can't
+        // have been reused
+        unsafe {
+            libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+        }
+
+        // Dropped from `update()`, which mustn't wait on the shell exiting
+        thread::spawn(move || {
+            let _ = child.wait();
+        });
+    }
+}
+
+/// Start `command` with `query` on its stdin, in a process group of its own
+pub fn spawn_filter(command: &str, query: &str) -> io::Result<(FilterProcess, ChildStdout)> {
+    let mut child = process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .process_group(0)
+        .spawn()?;
+
+    let stdin = child.stdin.take();
+    let stdout = child.stdout.take();
+    let process = FilterProcess(Some(child));
+
+    // A query fits the pipe's buffer, so this can't block on a command not reading it
+    if let Some(mut stdin) = stdin {
+        let _ = writeln!(stdin, "{}", query);
+    }
+
+    match stdout {
+        Some(stdout) => Ok((process, stdout)),
+        None => Err(io::Error::other("stdout not captured")),
+    }
+}
+
+/// Read a `filter_command`'s output until it exits and turn each line into a result
+///
+/// Lines naming a desktop ID show that app, `name<TAB>command` runs the command like
+/// a config entry's and anything else is opened with xdg-open, like a file path.
+pub fn filter_results(mut stdout: ChildStdout, applications: &[Application]) -> Vec<Application> {
+    let mut output = String::new();
+    if let Err(e) = stdout.read_to_string(&mut output) {
+        logger::warn!("Failed to read the filter command's output: {}", e);
+    }
+
+    output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            if let Some(application) = applications.iter().find(|app| app.id == line) {
+                return application.clone();
+            }
+
+            let id = format!("filter:{}", line);
+            match line.split_once('\t') {
+                Some((name, command)) => line_result(id, name, command),
+                None => Application {
+                    url: Some(line.to_string()),
+                    ..line_result(id, line, "")
+                },
+            }
+        })
+        .collect()
+}
+
+fn line_result(id: String, name: &str, action: &str) -> Application {
+    Application {
+        id,
+        source: Source::Script,
+        packaging: Packaging::Native,
+        name: name.to_string(),
+        transliterated_name: None,
+        url: None,
+        actions: Vec::new(),
+        from_action: false,
+        generic_name: None,
+        comment: None,
+        keywords: Vec::new(),
+        haystack: String::new(),
+        exec: action.to_string(),
+        icon: Icon::None,
+        icon_name: String::new(),
+        categories: Vec::new(),
+        terminal: false,
+        working_dir: None,
+        extras: HashMap::new(),
+        source_path: None,
+        wm_class: None,
+        dbus_activatable: false,
+    }
+}
+
+/// Act on a chosen script line and close like a launch does, or only log it in safe mode
+pub fn select(script: &ScriptConfig, item: &Application, config: &Config) {
+    if config.safe_mode {
+        logger::info!("Safe mode, not passing {} to {}", item.name, script.command);
+        return;
+    }
+
+    let mut shell = process::Command::new("sh");
+
+    if item.exec.is_empty() {
+        // Feed the line back to the script as its first argument, like rofi's script mode
+        shell
+            .arg("-c")
+            .arg(format!("{} \"$1\"", script.command))
+            .arg("sh")
+            .arg(&item.name);
+    } else {
+        shell.arg("-c").arg(&item.exec);
+    }
+
+    match shell.spawn() {
+        Ok(_) => launch::run_post_launch_hook(item, config),
+        Err(e) => logger::warn!("Failed to run script {}: {}", script.command, e),
+    }
+
+    launch::close(config);
+}
@@ -0,0 +1,76 @@
+use crate::config::SessionOverride;
+use crate::logger;
+use std::env;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Session {
+    Wayland,
+    X11,
+}
+
+impl Session {
+    /// The display server Astatine runs under, unless forced by config
+    pub fn detect(session_override: SessionOverride) -> Self {
+        let session = match session_override {
+            SessionOverride::Wayland => Session::Wayland,
+            SessionOverride::X11 => Session::X11,
+            SessionOverride::Auto => Self::from_env(),
+        };
+
+        logger::info!(
+            "Running in {:?} mode{}",
+            session,
+            if session_override == SessionOverride::Auto {
+                ""
+            } else {
+                " (forced by config)"
+            }
+        );
+
+        session
+    }
+
+    /// Whether any display server is advertised, winit panics instead of erroring without one
+    pub fn display_available() -> bool {
+        ["WAYLAND_DISPLAY", "WAYLAND_SOCKET", "DISPLAY"]
+            .into_iter()
+            .any(is_set)
+    }
+
+    fn from_env() -> Self {
+        // Winit prefers Wayland whenever a compositor is reachable
+        if is_set("WAYLAND_DISPLAY") || is_set("WAYLAND_SOCKET") {
+            return Session::Wayland;
+        }
+
+        if is_set("DISPLAY") {
+            return Session::X11;
+        }
+
+        match env::var("XDG_SESSION_TYPE").as_deref() {
+            Ok("wayland") => Session::Wayland,
+            _ => Session::X11,
+        }
+    }
+
+    /// How the window is opened, `transparent` for a translucent background
+    pub fn window_settings(self, transparent: bool) -> iced::window::Settings {
+        match self {
+            // Wayland clients can't position themselves or stay on top, the compositor decides
+            Session::Wayland => iced::window::Settings {
+                transparent,
+                ..Default::default()
+            },
+            Session::X11 => iced::window::Settings {
+                position: iced::window::Position::Centered,
+                level: iced::window::Level::AlwaysOnTop,
+                transparent,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+fn is_set(var: &str) -> bool {
+    env::var_os(var).is_some_and(|value| !value.is_empty())
+}
@@ -0,0 +1,138 @@
+use crate::apps::Application;
+use crate::logger;
+
+/// How a result line is composed, parsed once from `result_format`
+///
+/// Text before a field belongs to it, and so does text after the last field, so a
+/// missing field takes its separators with it: `{name} ({comment})` renders as
+/// just the name when there is no comment.
+#[derive(Debug, Clone)]
+pub struct Template {
+    pieces: Vec<(String, Field)>,
+    trailing: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    Name,
+    GenericName,
+    Comment,
+    Exec,
+    Id,
+    Categories,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "name" => Some(Self::Name),
+            "generic_name" => Some(Self::GenericName),
+            "comment" => Some(Self::Comment),
+            "exec" => Some(Self::Exec),
+            "id" => Some(Self::Id),
+            "categories" => Some(Self::Categories),
+            _ => None,
+        }
+    }
+
+    fn value(self, application: &Application) -> Option<String> {
+        let value = match self {
+            Self::Name => application.name.clone(),
+            Self::GenericName => application.generic_name.clone()?,
+            Self::Comment => application.comment.clone()?,
+            Self::Exec => application.exec.clone(),
+            Self::Id => application.id.clone(),
+            Self::Categories => application.categories.join(", "),
+        };
+
+        (!value.is_empty()).then_some(value)
+    }
+}
+
+impl Default for Template {
+    fn default() -> Self {
+        Self {
+            pieces: vec![(String::new(), Field::Name)],
+            trailing: String::new(),
+        }
+    }
+}
+
+impl Template {
+    /// Parse a format like `{name} — {generic_name}`, `{{` and `}}` being literal braces
+    pub fn parse(format: &str) -> Result<Self, String> {
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut chars = format.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest
+                        .find('}')
+                        .ok_or_else(|| format!("Unclosed {{ in {}", format))?;
+                    let name = &rest[..end];
+                    let field = Field::from_name(name)
+                        .ok_or_else(|| format!("Unknown field {{{}}}", name))?;
+
+                    pieces.push((std::mem::take(&mut literal), field));
+                    chars = rest[end + 1..].chars();
+                }
+                c => literal.push(c),
+            }
+        }
+
+        if pieces.is_empty() {
+            return Err(format!("{} references no field", format));
+        }
+
+        Ok(Self {
+            pieces,
+            trailing: literal,
+        })
+    }
+
+    /// The configured template, or plain names when it doesn't parse
+    pub fn parse_or_default(format: &str) -> Self {
+        Self::parse(format).unwrap_or_else(|e| {
+            logger::warn!("Invalid result_format: {}, showing names only", e);
+            Self::default()
+        })
+    }
+
+    pub fn render(&self, application: &Application) -> String {
+        let mut line = String::new();
+        let last = self.pieces.len() - 1;
+
+        for (i, (literal, field)) in self.pieces.iter().enumerate() {
+            let Some(value) = field.value(application) else {
+                continue;
+            };
+
+            // A missing first field doesn't leave the next one's separator leading the line
+            if i == 0 || !line.is_empty() {
+                line.push_str(literal);
+            }
+            line.push_str(&value);
+            if i == last {
+                line.push_str(&self.trailing);
+            }
+        }
+
+        // Every field missing still has to leave something to click on
+        if line.is_empty() {
+            return application.name.clone();
+        }
+
+        line
+    }
+}
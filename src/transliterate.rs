@@ -0,0 +1,20 @@
+use pinyin::ToPinyin;
+use wana_kana::ConvertJapanese;
+
+/// Latin spelling of a name with CJK characters, using romaji for kana and pinyin for
+/// ideographs, or `None` when there's nothing to transliterate
+pub fn transliterate(name: &str) -> Option<String> {
+    if name.is_ascii() {
+        return None;
+    }
+
+    let mut latin = String::with_capacity(name.len() * 2);
+    for c in name.to_romaji().chars() {
+        match c.to_pinyin() {
+            Some(pinyin) => latin.push_str(pinyin.plain()),
+            None => latin.push(c),
+        }
+    }
+
+    (latin != name).then_some(latin)
+}
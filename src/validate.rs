@@ -0,0 +1,97 @@
+use crate::apps::{Icon, build_applications, locales};
+use crate::config::{Config, XKeyBehavior};
+use crate::exec::is_executable;
+use freedesktop_desktop_entry::DesktopEntry;
+use std::path::Path;
+
+/// Letters allowed after `%` in an Exec line, including the deprecated ones
+const FIELD_CODES: &[char] = &[
+    'f', 'F', 'u', 'U', 'i', 'c', 'k', 'd', 'D', 'n', 'N', 'v', 'm', '%',
+];
+
+/// What `--validate` found out about a single `.desktop` file
+pub struct Report {
+    /// Whether the entry ends up in the result list
+    pub shown: bool,
+    pub problems: Vec<String>,
+}
+
+impl Report {
+    pub fn passed(&self) -> bool {
+        self.shown && self.problems.is_empty()
+    }
+}
+
+/// Parse a `.desktop` file and check it the way the launcher would read it
+pub fn validate(path: &Path, config: &Config) -> Result<Report, String> {
+    let locales = locales();
+    let entry = DesktopEntry::from_path(path, Some(&locales))
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+
+    let mut problems = Vec::new();
+
+    if entry.name(&locales).is_none_or(|name| name.is_empty()) {
+        problems.push(String::from("Missing Name"));
+    }
+
+    match entry.type_() {
+        Some("Directory") => problems.push(String::from("Type=Directory is not an application")),
+        Some("Link") => {
+            if entry.desktop_entry("URL").is_none_or(str::is_empty) {
+                problems.push(String::from("Type=Link without a URL"));
+            }
+        }
+        _ => match entry.exec().filter(|exec| !exec.is_empty()) {
+            Some(exec) => problems.extend(field_code_problems(exec)),
+            // Started over D-Bus, Exec is only a fallback for those
+            None if entry.desktop_entry("DBusActivatable") == Some("true") => (),
+            None => problems.push(String::from("Missing Exec")),
+        },
+    }
+
+    if entry.no_display() {
+        problems.push(String::from("NoDisplay is set, most launchers hide it"));
+    }
+
+    let missing_try_exec = entry
+        .desktop_entry("TryExec")
+        .filter(|program| !is_executable(program));
+    if let Some(try_exec) = missing_try_exec {
+        problems.push(format!("TryExec {} is not an installed program", try_exec));
+    }
+
+    for (key, behavior) in &config.x_keys {
+        let hidden = *behavior == XKeyBehavior::Hide
+            && entry
+                .desktop_entry(key)
+                .is_some_and(|value| value.trim() == "true");
+        if hidden {
+            problems.push(format!("Hidden by {} in the config's x_keys", key));
+        }
+    }
+
+    // The same conversion the launcher runs, so the verdict can't drift from it
+    let shown = !build_applications(vec![entry], &locales, config, &Icon::None).is_empty();
+
+    Ok(Report { shown, problems })
+}
+
+/// `%` sequences in an Exec line the spec doesn't define
+fn field_code_problems(exec: &str) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut chars = exec.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+
+        match chars.next() {
+            Some(code) if FIELD_CODES.contains(&code) => (),
+            Some(code) => problems.push(format!("Exec has an unknown field code %{}", code)),
+            None => problems.push(String::from("Exec ends in a lone %")),
+        }
+    }
+
+    problems
+}
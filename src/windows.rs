@@ -0,0 +1,492 @@
+use crate::apps::{Application, Packaging, Source};
+use crate::launch;
+use crate::logger;
+use crate::session::Session;
+use std::collections::HashMap;
+
+/// Open windows per lowercased WM class or Wayland app ID
+#[derive(Debug, Clone)]
+pub struct WindowCounts {
+    session: Session,
+    counts: HashMap<String, usize>,
+}
+
+impl Default for WindowCounts {
+    fn default() -> Self {
+        Self {
+            session: Session::X11,
+            counts: HashMap::new(),
+        }
+    }
+}
+
+impl WindowCounts {
+    /// Ask the display server which windows are open, empty when it can't tell
+    pub fn query(session: Session) -> Self {
+        let classes = match session {
+            Session::X11 => x11::window_classes(),
+            Session::Wayland => wayland::app_ids(),
+        };
+
+        let mut counts = HashMap::new();
+        match classes {
+            Ok(classes) => {
+                for class in classes {
+                    *counts.entry(class.to_lowercase()).or_insert(0) += 1;
+                }
+            }
+            Err(e) => logger::debug!("Failed to list open windows: {}", e),
+        }
+
+        Self { session, counts }
+    }
+
+    /// Windows belonging to `application`, matched by StartupWMClass, desktop ID or program
+    pub fn of(&self, application: &Application) -> usize {
+        window_keys(application)
+            .iter()
+            .find_map(|key| self.counts.get(key))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Bring one of `application`'s windows to the front, false when it has none open
+    /// or the window manager won't
+    ///
+    /// D-Bus activatable apps are asked to activate themselves instead, which single
+    /// instance apps answer by raising their window.
+    pub fn raise(&self, application: &Application) -> bool {
+        if self.of(application) == 0 {
+            return false;
+        }
+
+        if application.dbus_activatable && launch::activate_over_dbus(&application.id) {
+            return true;
+        }
+
+        let keys = window_keys(application);
+        let raised = match self.session {
+            Session::X11 => x11::activate(&keys),
+            Session::Wayland => wayland::activate(&keys),
+        };
+
+        raised.unwrap_or_else(|e| {
+            logger::debug!("Failed to raise {}: {}", application.id, e);
+            false
+        })
+    }
+}
+
+/// Open windows as results for the `--windows` switcher, named by their title and
+/// described and iconed like the app among `applications` they belong to
+pub fn list(session: Session, applications: &[Application]) -> Vec<Application> {
+    let windows = match session {
+        Session::X11 => x11::windows(),
+        Session::Wayland => wayland::windows(),
+    };
+    let windows = windows.unwrap_or_else(|e| {
+        logger::warn!("Failed to list open windows: {}", e);
+        Vec::new()
+    });
+
+    windows
+        .into_iter()
+        .map(|(key, class, title)| {
+            let lowercase = class.to_lowercase();
+            let app = applications
+                .iter()
+                .find(|application| window_keys(application).contains(&lowercase));
+
+            Application {
+                id: format!("window:{}", key),
+                source: Source::Window,
+                packaging: app.map_or(Packaging::Native, |app| app.packaging),
+                name: title,
+                transliterated_name: None,
+                generic_name: Some(app.map_or_else(|| class.clone(), |app| app.name.clone())),
+                comment: None,
+                keywords: Vec::new(),
+                haystack: String::new(),
+                exec: String::new(),
+                url: None,
+                actions: Vec::new(),
+                from_action: false,
+                icon: app.map(|app| app.icon.clone()).unwrap_or_default(),
+                icon_name: app.map_or(lowercase, |app| app.icon_name.clone()),
+                categories: app.map(|app| app.categories.clone()).unwrap_or_default(),
+                terminal: false,
+                working_dir: None,
+                extras: HashMap::new(),
+                source_path: None,
+                wm_class: Some(class),
+                dbus_activatable: false,
+            }
+        })
+        .collect()
+}
+
+/// Bring the window a `list` result stands for to the front
+pub fn activate(session: Session, window: &Application) -> bool {
+    let activated = match session {
+        Session::X11 => window
+            .id
+            .strip_prefix("window:")
+            .and_then(|id| id.parse().ok())
+            .ok_or_else(|| format!("{} is not a window", window.id))
+            .and_then(x11::activate_window),
+        Session::Wayland => {
+            wayland::activate_toplevel(window.wm_class.as_deref().unwrap_or_default(), &window.name)
+        }
+    };
+
+    activated.unwrap_or_else(|e| {
+        logger::warn!("Failed to activate {}: {}", window.name, e);
+        false
+    })
+}
+
+/// The lowercased classes `application`'s windows may have, most specific first
+fn window_keys(application: &Application) -> Vec<String> {
+    let program = application
+        .exec
+        .split_whitespace()
+        .next()
+        .and_then(|program| program.rsplit('/').next());
+
+    [
+        application.wm_class.as_deref(),
+        Some(&application.id),
+        program,
+    ]
+    .into_iter()
+    .flatten()
+    .map(str::to_lowercase)
+    .collect()
+}
+
+mod x11 {
+    use x11rb::connection::Connection;
+    use x11rb::properties::WmClass;
+    use x11rb::protocol::xproto::{
+        Atom, AtomEnum, ClientMessageEvent, ConnectionExt, EventMask, Window,
+    };
+
+    /// The class of every window in the EWMH `_NET_CLIENT_LIST`
+    pub fn window_classes() -> Result<Vec<String>, String> {
+        let (conn, screen) = x11rb::connect(None).map_err(|e| e.to_string())?;
+        let root = conn.setup().roots[screen].root;
+
+        Ok(client_windows(&conn, root)?
+            .into_iter()
+            .map(|(_, class)| class)
+            .collect())
+    }
+
+    /// Every managed window's ID, class and title, newest first
+    pub fn windows() -> Result<Vec<(String, String, String)>, String> {
+        let (conn, screen) = x11rb::connect(None).map_err(|e| e.to_string())?;
+        let root = conn.setup().roots[screen].root;
+        let net_wm_name = atom(&conn, b"_NET_WM_NAME")?;
+        let utf8_string = atom(&conn, b"UTF8_STRING")?;
+
+        Ok(client_windows(&conn, root)?
+            .into_iter()
+            .rev()
+            .map(|(window, class)| {
+                // Old clients only set the Latin-1 WM_NAME
+                let title = property(&conn, window, net_wm_name, utf8_string)
+                    .or_else(|| {
+                        property(
+                            &conn,
+                            window,
+                            AtomEnum::WM_NAME.into(),
+                            AtomEnum::STRING.into(),
+                        )
+                    })
+                    .unwrap_or_else(|| class.clone());
+                (window.to_string(), class, title)
+            })
+            .collect())
+    }
+
+    /// Ask the window manager to activate the newest window with one of the `classes`
+    pub fn activate(classes: &[String]) -> Result<bool, String> {
+        let (conn, screen) = x11rb::connect(None).map_err(|e| e.to_string())?;
+        let root = conn.setup().roots[screen].root;
+
+        let windows = client_windows(&conn, root)?;
+        let Some(window) = classes.iter().find_map(|key| {
+            windows
+                .iter()
+                .rev()
+                .find(|(_, class)| class.to_lowercase() == *key)
+                .map(|(window, _)| *window)
+        }) else {
+            return Ok(false);
+        };
+
+        request_activation(&conn, root, window)?;
+        Ok(true)
+    }
+
+    /// Ask the window manager to activate `window`
+    pub fn activate_window(window: Window) -> Result<bool, String> {
+        let (conn, screen) = x11rb::connect(None).map_err(|e| e.to_string())?;
+        let root = conn.setup().roots[screen].root;
+
+        request_activation(&conn, root, window)?;
+        Ok(true)
+    }
+
+    fn request_activation(
+        conn: &impl Connection,
+        root: Window,
+        window: Window,
+    ) -> Result<(), String> {
+        // Source 2 is a pager, which window managers trust to take focus
+        let active_window = atom(conn, b"_NET_ACTIVE_WINDOW")?;
+        let event = ClientMessageEvent::new(32, window, active_window, [2, 0, 0, 0, 0]);
+        conn.send_event(
+            false,
+            root,
+            EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+            event,
+        )
+        .map_err(|e| e.to_string())?;
+        conn.flush().map_err(|e| e.to_string())
+    }
+
+    /// Managed windows in the order they were mapped, with their WM class
+    fn client_windows(
+        conn: &impl Connection,
+        root: Window,
+    ) -> Result<Vec<(Window, String)>, String> {
+        let client_list = atom(conn, b"_NET_CLIENT_LIST")?;
+        let windows = conn
+            .get_property(false, root, client_list, AtomEnum::WINDOW, 0, u32::MAX)
+            .map_err(|e| e.to_string())?
+            .reply()
+            .map_err(|e| e.to_string())?;
+        let Some(windows) = windows.value32() else {
+            return Err(String::from("the window manager doesn't support EWMH"));
+        };
+
+        Ok(windows
+            .filter_map(|window| {
+                let class = WmClass::get(conn, window).ok()?.reply().ok()??;
+                Some((window, String::from_utf8_lossy(class.class()).into_owned()))
+            })
+            .collect())
+    }
+
+    /// A text property of `window`, `None` when unset or empty
+    fn property(conn: &impl Connection, window: Window, name: Atom, kind: Atom) -> Option<String> {
+        let reply = conn
+            .get_property(false, window, name, kind, 0, u32::MAX)
+            .ok()?
+            .reply()
+            .ok()?;
+
+        Some(String::from_utf8_lossy(&reply.value).into_owned()).filter(|value| !value.is_empty())
+    }
+
+    fn atom(conn: &impl Connection, name: &[u8]) -> Result<Atom, String> {
+        Ok(conn
+            .intern_atom(false, name)
+            .map_err(|e| e.to_string())?
+            .reply()
+            .map_err(|e| e.to_string())?
+            .atom)
+    }
+}
+
+mod wayland {
+    use std::collections::HashMap;
+    use wayland_client::backend::ObjectId;
+    use wayland_client::globals::{GlobalList, GlobalListContents, registry_queue_init};
+    use wayland_client::protocol::wl_registry::WlRegistry;
+    use wayland_client::protocol::wl_seat::{self, WlSeat};
+    use wayland_client::{
+        Connection, Dispatch, EventQueue, Proxy, QueueHandle, event_created_child,
+    };
+    use wayland_protocols_wlr::foreign_toplevel::v1::client::zwlr_foreign_toplevel_handle_v1::{
+        self, ZwlrForeignToplevelHandleV1,
+    };
+    use wayland_protocols_wlr::foreign_toplevel::v1::client::zwlr_foreign_toplevel_manager_v1::{
+        self, ZwlrForeignToplevelManagerV1,
+    };
+
+    /// Toplevels that sent their app ID or title, by object
+    #[derive(Default)]
+    struct Toplevels {
+        handles: HashMap<ObjectId, Toplevel>,
+    }
+
+    struct Toplevel {
+        handle: ZwlrForeignToplevelHandleV1,
+        app_id: String,
+        title: String,
+    }
+
+    /// The app ID of every toplevel announced by wlr-foreign-toplevel-management
+    ///
+    /// GNOME doesn't implement the protocol, its windows go uncounted.
+    pub fn app_ids() -> Result<Vec<String>, String> {
+        let (_, _, _, toplevels) = toplevels()?;
+
+        Ok(toplevels
+            .handles
+            .into_values()
+            .map(|toplevel| toplevel.app_id)
+            .filter(|app_id| !app_id.is_empty())
+            .collect())
+    }
+
+    /// Every toplevel's position, app ID and title, ordered by title as the protocol
+    /// has no stacking order
+    pub fn windows() -> Result<Vec<(String, String, String)>, String> {
+        let (_, _, _, toplevels) = toplevels()?;
+
+        let mut windows: Vec<(String, String)> = toplevels
+            .handles
+            .into_values()
+            .map(|toplevel| (toplevel.app_id, toplevel.title))
+            .collect();
+        windows.sort_by(|(a_id, a), (b_id, b)| a.cmp(b).then_with(|| a_id.cmp(b_id)));
+
+        Ok(windows
+            .into_iter()
+            .enumerate()
+            .map(|(i, (app_id, title))| (i.to_string(), app_id, title))
+            .collect())
+    }
+
+    /// Ask the compositor to activate a toplevel with one of the `app_ids`
+    pub fn activate(app_ids: &[String]) -> Result<bool, String> {
+        activate_first(|toplevel| {
+            app_ids
+                .iter()
+                .any(|key| toplevel.app_id.to_lowercase() == *key)
+        })
+    }
+
+    /// Ask the compositor to activate the toplevel with this app ID and title, handles
+    /// don't outlive the connection that listed it
+    pub fn activate_toplevel(app_id: &str, title: &str) -> Result<bool, String> {
+        activate_first(|toplevel| toplevel.app_id == app_id && toplevel.title == title)
+    }
+
+    fn activate_first(matches: impl Fn(&Toplevel) -> bool) -> Result<bool, String> {
+        let (conn, globals, mut queue, mut toplevels) = toplevels()?;
+
+        let Some(handle) = toplevels
+            .handles
+            .values()
+            .find(|toplevel| matches(toplevel))
+            .map(|toplevel| toplevel.handle.clone())
+        else {
+            return Ok(false);
+        };
+
+        let seat: WlSeat = globals
+            .bind(&queue.handle(), 1..=1, ())
+            .map_err(|e| e.to_string())?;
+        handle.activate(&seat);
+        queue.roundtrip(&mut toplevels).map_err(|e| e.to_string())?;
+        conn.flush().map_err(|e| e.to_string())?;
+
+        Ok(true)
+    }
+
+    fn toplevels() -> Result<(Connection, GlobalList, EventQueue<Toplevels>, Toplevels), String> {
+        let conn = Connection::connect_to_env().map_err(|e| e.to_string())?;
+        let (globals, mut queue) =
+            registry_queue_init::<Toplevels>(&conn).map_err(|e| e.to_string())?;
+        let _manager: ZwlrForeignToplevelManagerV1 = globals
+            .bind(&queue.handle(), 1..=3, ())
+            .map_err(|e| e.to_string())?;
+
+        // The first roundtrip announces the toplevels, the second their app IDs
+        let mut toplevels = Toplevels::default();
+        for _ in 0..2 {
+            queue.roundtrip(&mut toplevels).map_err(|e| e.to_string())?;
+        }
+
+        Ok((conn, globals, queue, toplevels))
+    }
+
+    impl Dispatch<WlRegistry, GlobalListContents> for Toplevels {
+        fn event(
+            _: &mut Self,
+            _: &WlRegistry,
+            _: <WlRegistry as Proxy>::Event,
+            _: &GlobalListContents,
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+        }
+    }
+
+    impl Dispatch<WlSeat, ()> for Toplevels {
+        fn event(
+            _: &mut Self,
+            _: &WlSeat,
+            _: wl_seat::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+        }
+    }
+
+    impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for Toplevels {
+        fn event(
+            _: &mut Self,
+            _: &ZwlrForeignToplevelManagerV1,
+            _: zwlr_foreign_toplevel_manager_v1::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+        }
+
+        event_created_child!(Toplevels, ZwlrForeignToplevelManagerV1, [
+            zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+        ]);
+    }
+
+    impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for Toplevels {
+        fn event(
+            toplevels: &mut Self,
+            handle: &ZwlrForeignToplevelHandleV1,
+            event: zwlr_foreign_toplevel_handle_v1::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+            if let zwlr_foreign_toplevel_handle_v1::Event::Closed = event {
+                toplevels.handles.remove(&handle.id());
+                return;
+            }
+
+            let toplevel = toplevels
+                .handles
+                .entry(handle.id())
+                .or_insert_with(|| Toplevel {
+                    handle: handle.clone(),
+                    app_id: String::new(),
+                    title: String::new(),
+                });
+
+            match event {
+                zwlr_foreign_toplevel_handle_v1::Event::AppId { app_id } => {
+                    toplevel.app_id = app_id;
+                }
+                zwlr_foreign_toplevel_handle_v1::Event::Title { title } => {
+                    toplevel.title = title;
+                }
+                _ => (),
+            }
+        }
+    }
+}